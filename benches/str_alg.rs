@@ -0,0 +1,68 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use b24::hsh::str::{
+    str_alg::{IStrAlg, StrAlg},
+    str_quality::{StrHasher, StrQuality}
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+const SAMPLES: usize = 10_000;
+const SEED: u64 = 1;
+const LENS: [usize; 4] = [4, 16, 64, 256];
+
+/// Every StrAlg facet and the reference over the strs StrQuality tests
+fn str_hashers(c: &mut Criterion) {
+    let quality = StrQuality::new(SAMPLES, SEED);
+    let mut group = c.benchmark_group("str_hashers");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    (0..8)
+        .map(StrHasher::Facet)
+        .chain([StrHasher::Reference])
+        .for_each(|hasher| {
+            group.bench_with_input(BenchmarkId::from_parameter(format!("{hasher:?}")), &hasher, |b, hasher| {
+                b.iter(|| {
+                    quality
+                        .strs()
+                        .iter()
+                        .fold(0u64, |sum, str| sum.wrapping_add(hasher.hash(black_box(str))))
+                })
+            });
+        });
+    group.finish();
+}
+
+/// The raw_id by str length
+fn str_lens(c: &mut Criterion) {
+    let mut group = c.benchmark_group("str_lens");
+    LENS.iter().for_each(|len| {
+        let str: String = (0..*len).map(|at| (b'!' + (at % 94) as u8) as char).collect();
+        group.throughput(Throughput::Bytes(*len as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(len), &str, |b, str| {
+            b.iter(|| StrAlg::make_id(black_box(str), 0))
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, str_hashers, str_lens);
+criterion_main!(benches);
@@ -0,0 +1,110 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use b24::hsh::{
+    dash_trie::DashTrie,
+    hash_trie::IHashTrie,
+    id::num_id::NumID,
+    obj::sub_hsh_obj::SubHshObj,
+    park_trie::ParkTrie
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::prelude::*;
+use std::{
+    hint::black_box,
+    sync::{Arc, RwLock}
+};
+
+type Obj = SubHshObj<NumID>;
+type Dash = DashTrie<Obj, NumID>;
+type Park = ParkTrie<Obj, NumID>;
+
+const OBJS: [u64; 2] = [1_000, 100_000];
+/// the shard count ParkTrie had before it was sized by core count
+const FIXED_SHARDS: usize = 16;
+/// past 128 shards every key in a shard would share its hashbrown tag if the shard bits were reused
+const MANY_SHARDS: usize = 1024;
+
+/// Stores rawids 1 to objs and looks up twice as many, so half miss
+fn lookups(objs: u64) -> (Dash, Park, Park, Park, Vec<NumID>) {
+    let dash = Dash::new();
+    let park = Park::new();
+    let fixed = Park::with_shards(FIXED_SHARDS);
+    let many = Park::with_shards(MANY_SHARDS);
+    (1..=objs).for_each(|raw| {
+        dash.add(Arc::new(RwLock::new(Obj::new(raw))));
+        park.add(Arc::new(RwLock::new(Obj::new(raw))));
+        fixed.add(Arc::new(RwLock::new(Obj::new(raw))));
+        many.add(Arc::new(RwLock::new(Obj::new(raw))));
+    });
+    (dash, park, fixed, many, (1..=objs * 2).map(NumID::new).collect())
+}
+
+/// DashTrie::get against the by-ref read paths of DashTrie and ParkTrie, on one thread
+fn trie_get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trie_get");
+    OBJS.iter().for_each(|objs| {
+        let (dash, park, fixed, many, ids) = lookups(*objs);
+        let locked_ids: Vec<Arc<RwLock<NumID>>> = ids.iter().map(|id| Arc::new(RwLock::new(id.clone()))).collect();
+        group.throughput(Throughput::Elements(ids.len() as u64));
+        group.bench_function(BenchmarkId::new("DashTrie get", objs), |b| {
+            b.iter(|| locked_ids.iter().filter(|id| dash.get(black_box((*id).clone())).is_some()).count())
+        });
+        group.bench_function(BenchmarkId::new("DashTrie get_by", objs), |b| {
+            b.iter(|| ids.iter().filter(|id| dash.get_by(black_box(id)).is_some()).count())
+        });
+        group.bench_function(BenchmarkId::new("ParkTrie get_by", objs), |b| {
+            b.iter(|| ids.iter().filter(|id| park.get_by(black_box(id)).is_some()).count())
+        });
+        group.bench_function(BenchmarkId::new("ParkTrie get_by, 16 shards", objs), |b| {
+            b.iter(|| ids.iter().filter(|id| fixed.get_by(black_box(id)).is_some()).count())
+        });
+        group.bench_function(BenchmarkId::new("ParkTrie get_by, 1024 shards", objs), |b| {
+            b.iter(|| ids.iter().filter(|id| many.get_by(black_box(id)).is_some()).count())
+        });
+    });
+    group.finish();
+}
+
+/// The same lookups spread over rayon, where shard contention shows
+fn trie_get_rayon(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trie_get_rayon");
+    OBJS.iter().for_each(|objs| {
+        let (dash, park, fixed, many, ids) = lookups(*objs);
+        group.throughput(Throughput::Elements(ids.len() as u64));
+        group.bench_function(BenchmarkId::new("DashTrie get_by", objs), |b| {
+            b.iter(|| ids.par_iter().filter(|id| dash.get_by(black_box(id)).is_some()).count())
+        });
+        group.bench_function(BenchmarkId::new("ParkTrie get_by", objs), |b| {
+            b.iter(|| ids.par_iter().filter(|id| park.get_by(black_box(id)).is_some()).count())
+        });
+        group.bench_function(BenchmarkId::new("ParkTrie get_by, 16 shards", objs), |b| {
+            b.iter(|| ids.par_iter().filter(|id| fixed.get_by(black_box(id)).is_some()).count())
+        });
+        group.bench_function(BenchmarkId::new("ParkTrie get_by, 1024 shards", objs), |b| {
+            b.iter(|| ids.par_iter().filter(|id| many.get_by(black_box(id)).is_some()).count())
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, trie_get, trie_get_rayon);
+criterion_main!(benches);
@@ -0,0 +1,148 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{
+    bit_cell_map::{BitCellErr, BitCellMap},
+    sub_hsh_alg::{SubHshAlg, SubHshAlgErr, MAX_BIT}
+};
+use arbitrary::{Arbitrary, Unstructured};
+use std::sync::Arc;
+
+/// A layout as a data file could describe it
+#[derive(Arbitrary, Debug)]
+pub struct LayoutSpec {
+    pub widths: Vec<u8>,
+    pub sub_hsh_bits: u8,
+    pub values: Vec<u64>,
+}
+
+/// Fuzz target body, panics only when an invariant breaks:<br>
+/// a bad layout must be an Err, never a panic,
+/// and a good layout must round trip pack and unpack.<br>
+/// Run it with `cargo fuzz run bit_cell_map`.
+pub fn fuzz_layout(data: &[u8]) {
+    let spec = match LayoutSpec::arbitrary(&mut Unstructured::new(data)) {
+        Ok(spec) => spec,
+        Err(_) => return,
+    };
+    check_layout(&spec);
+}
+
+pub fn check_layout(spec: &LayoutSpec) {
+    let bits_sum: u32 = spec.widths.iter().map(|bits| *bits as u32).sum();
+    let valid = spec.widths.len() <= 64
+        && spec.widths.iter().all(|bits| *bits <= 64)
+        && bits_sum <= 64;
+
+    match BitCellMap::try_new(&Arc::new(spec.widths.clone())) {
+        Ok(map) => {
+            assert!(valid, "{:?} should not make a BitCellMap", spec.widths);
+            check_pack(&map, &spec.values);
+        }
+        Err(err) => {
+            assert!(!valid, "{:?} should make a BitCellMap, not {}", spec.widths, err);
+        }
+    }
+
+    let bits_ok = SubHshAlg::check_bits(spec.sub_hsh_bits).is_ok();
+    assert_eq!(bits_ok, (1..=MAX_BIT).contains(&spec.sub_hsh_bits));
+}
+
+fn check_pack(map: &BitCellMap, values: &[u64]) {
+    let cells = map.cells() as usize;
+    let values: Vec<u64> = (0..cells)
+        .map(|cell| values.get(cell).cloned().unwrap_or_default())
+        .collect();
+    let fits = values
+        .iter()
+        .enumerate()
+        .all(|(cell, value)| value & !map.and_mask_cell(cell as u8) == 0);
+    assert_eq!(map.pack(&values).is_some(), fits);
+
+    let masked: Vec<u64> = values
+        .iter()
+        .enumerate()
+        .map(|(cell, value)| value & map.and_mask_cell(cell as u8))
+        .collect();
+    let raw = map.pack(&masked).expect("masked values always fit");
+    assert_eq!(map.unpack(raw), masked);
+    if cells > 0 {
+        assert!(map.pack(&masked[1..]).is_none());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prng::{fast_prng::FastPrng, prng_trait::IPrng};
+
+    /// Runs fuzz_layout on FastPrng bytes, so the tests need no fuzzer
+    fn fuzz_layouts(runs: usize, seed: u64) {
+        let mut prng = FastPrng::new();
+        prng.seed(seed);
+        (0..runs).for_each(|_| {
+            let len = (prng.u64() % 512) as usize;
+            let data: Vec<u8> = (0..len).map(|_| prng.u64() as u8).collect();
+            fuzz_layout(&data);
+        });
+    }
+
+    #[test]
+    fn fuzzed_layouts_hold_invariants() {
+        (0..8).for_each(|seed| fuzz_layouts(2_000, seed));
+    }
+
+    #[test]
+    fn too_many_cells() {
+        let widths = Arc::new(vec![1u8; 65]);
+        assert_eq!(BitCellMap::try_new(&widths).err(), Some(BitCellErr::TooManyCells(65)));
+    }
+
+    #[test]
+    fn cell_too_wide() {
+        let widths = Arc::new(vec![8u8, 65]);
+        assert_eq!(BitCellMap::try_new(&widths).err(), Some(BitCellErr::CellTooWide { cell: 1, bits: 65 }));
+    }
+
+    #[test]
+    fn too_many_bits() {
+        let widths = Arc::new(vec![32u8, 32, 1]);
+        assert_eq!(BitCellMap::try_new(&widths).err(), Some(BitCellErr::TooManyBits(65)));
+    }
+
+    #[test]
+    fn full_layouts_are_ok() {
+        [vec![64u8], vec![1u8; 64], vec![]].into_iter().for_each(|widths| {
+            check_layout(&LayoutSpec { widths, sub_hsh_bits: MAX_BIT, values: vec![u64::MAX; 64] });
+        });
+    }
+
+    #[test]
+    fn sub_hsh_too_many_bits() {
+        assert_eq!(SubHshAlg::check_bits(MAX_BIT + 1), Err(SubHshAlgErr::TooManyBits(MAX_BIT + 1)));
+        let map = Arc::new(BitCellMap::new(&Arc::new(vec![64u8])));
+        assert_eq!(
+            SubHshAlg::try_register("fuzz_too_wide", MAX_BIT + 1, &map).err(),
+            Some(SubHshAlgErr::TooManyBits(MAX_BIT + 1))
+        );
+        assert!(SubHshAlg::try_register("fuzz_max", MAX_BIT, &map).is_ok());
+    }
+}
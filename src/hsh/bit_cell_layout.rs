@@ -0,0 +1,249 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+/// Declares a NumID with named BitCellMap fields, cell 0 in the lowest bits.<br>
+/// Each field names its getter, its setter and its width in bits:
+/// ```
+/// use b24::hsh::bit_cell_layout::bit_cell_layout;
+///
+/// bit_cell_layout! {
+///     pub struct TileID {
+///         layer, set_layer: 4,
+///         x, set_x: 20,
+///         y, set_y: 20,
+///     }
+/// }
+///
+/// let mut tile = TileID::new(2, 300, 400).unwrap();
+/// assert!(tile.set_x(301));
+/// assert_eq!((tile.layer(), tile.x(), tile.y()), (2, 301, 400));
+/// assert!(!tile.set_layer(16));
+/// ```
+/// The 64-bit budget is checked when compiled.<br>
+/// Setters return false if the value overflows its field.<br>
+/// The declaring crate needs serde, the layout derives its traits.
+#[macro_export]
+macro_rules! bit_cell_layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field:ident, $set_field:ident: $bits:expr),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Default, PartialEq, Eq, Hash, Debug, ::serde::Serialize, ::serde::Deserialize)]
+        #[serde(transparent)]
+        $vis struct $name {
+            id: $crate::hsh::id::num_id::NumID,
+        }
+
+        const _: () = {
+            let widths: &[u8] = &[$($bits),+];
+            assert!(widths.len() <= 64, concat!(stringify!($name), " has more than 64 cells!"));
+            let mut sum: u32 = 0;
+            let mut cell = 0;
+            while cell < widths.len() {
+                assert!(widths[cell] <= 64, concat!(stringify!($name), " has a field wider than 64 bits!"));
+                sum += widths[cell] as u32;
+                cell += 1;
+            }
+            assert!(sum <= 64, concat!(stringify!($name), " has more than 64 bits of fields!"));
+        };
+
+        impl $name {
+            /// the field names in cell order
+            pub const FIELDS: &'static [&'static str] = &[$(stringify!($field)),+];
+            /// the field widths in cell order
+            pub const WIDTHS: &'static [u8] = &[$($bits),+];
+
+            pub fn cell_map() -> &'static ::std::sync::Arc<$crate::hsh::bit_cell_map::BitCellMap> {
+                static MAP: ::std::sync::LazyLock<::std::sync::Arc<$crate::hsh::bit_cell_map::BitCellMap>> =
+                    ::std::sync::LazyLock::new(|| ::std::sync::Arc::new(
+                        $crate::hsh::bit_cell_map::BitCellMap::new(&::std::sync::Arc::new($name::WIDTHS.to_vec()))
+                    ));
+                &MAP
+            }
+
+            /// Returns None if a value overflows its field
+            #[allow(clippy::too_many_arguments)]
+            pub fn new($($field: u64),+) -> Option<Self> {
+                Self::cell_map().pack(&[$($field),+]).map(Self::from_raw)
+            }
+
+            pub fn from_raw(raw: u64) -> Self {
+                Self { id: $crate::hsh::id::num_id::NumID::new(raw) }
+            }
+
+            pub fn from_id(id: $crate::hsh::id::num_id::NumID) -> Self {
+                Self { id }
+            }
+
+            pub fn id(&self) -> &$crate::hsh::id::num_id::NumID {
+                &self.id
+            }
+
+            /// the cell of a field by name
+            pub fn cell_of(field: &str) -> Option<u8> {
+                Self::FIELDS.iter().position(|name| *name == field).map(|cell| cell as u8)
+            }
+
+            /// Reads a field by name, None if there is no such field
+            pub fn get(&self, field: &str) -> Option<u64> {
+                let raw = $crate::hsh::id::num_id::INumID::raw_id(&self.id);
+                Self::cell_of(field).map(|cell| Self::cell_map().get(raw, cell))
+            }
+
+            /// Writes a field by name<br>
+            /// returns false if there is no such field or the value overflows it
+            pub fn set(&mut self, field: &str, value: u64) -> bool {
+                match Self::cell_of(field) {
+                    Some(cell) => self.set_cell_value(cell, value),
+                    None => false,
+                }
+            }
+
+            fn set_cell_value(&mut self, cell: u8, value: u64) -> bool {
+                let raw = $crate::hsh::id::num_id::INumID::raw_id(&self.id);
+                match Self::cell_map().set(raw, cell, value) {
+                    Some(raw) => {
+                        self.id = $crate::hsh::id::num_id::NumID::new(raw);
+                        true
+                    }
+                    None => false,
+                }
+            }
+
+            $crate::bit_cell_layout!(@fields 0u8; $($field, $set_field;)+);
+        }
+
+        impl From<$name> for $crate::hsh::id::num_id::NumID {
+            fn from(layout: $name) -> Self {
+                layout.id
+            }
+        }
+
+        impl $crate::hsh::sub_hsh_alg::IUseSubHshAlg for $name {
+            fn alg(&self) -> &::std::sync::Arc<$crate::hsh::sub_hsh_alg::SubHshAlg> {
+                $crate::hsh::sub_hsh_alg::IUseSubHshAlg::alg(&self.id)
+            }
+        }
+
+        impl $crate::hsh::id::num_id::INumID for $name {
+            fn as_any(&self) -> &(dyn ::std::any::Any) { self }
+
+            fn raw_id(&self) -> u64 {
+                $crate::hsh::id::num_id::INumID::raw_id(&self.id)
+            }
+
+            fn raw_facet(&self, facet: u8) -> u64 {
+                $crate::hsh::id::num_id::INumID::raw_facet(&self.id, facet)
+            }
+
+            fn same_id(&self, id: &::std::sync::Arc<::std::sync::RwLock<dyn $crate::hsh::id::num_id::INumID>>) -> bool {
+                $crate::hsh::id::num_id::INumID::same_id(&self.id, id)
+            }
+
+            fn has_facets(&self) -> bool { false }
+
+            fn new_0_facet_id(raw: u64) -> Self {
+                Self::from_raw(raw)
+            }
+        }
+    };
+    (@fields $cell:expr; $field:ident, $set_field:ident; $($rest:tt)*) => {
+        pub fn $field(&self) -> u64 {
+            Self::cell_map().get($crate::hsh::id::num_id::INumID::raw_id(&self.id), $cell)
+        }
+
+        pub fn $set_field(&mut self, value: u64) -> bool {
+            self.set_cell_value($cell, value)
+        }
+
+        $crate::bit_cell_layout!(@fields $cell + 1; $($rest)*);
+    };
+    (@fields $cell:expr;) => {};
+}
+
+pub use crate::bit_cell_layout;
+
+#[cfg(test)]
+mod tests {
+    use crate::hsh::{
+        id::num_id::{INumID, NumID},
+        sub_hsh_alg::IUseSubHshAlg
+    };
+
+    bit_cell_layout! {
+        /// Leaves the top 20 bits unused
+        struct Probe {
+            kind, set_kind: 4,
+            x, set_x: 20,
+            y, set_y: 20,
+        }
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        let mut probe = Probe::new(3, 1000, 70_000).unwrap();
+        assert_eq!((probe.kind(), probe.x(), probe.y()), (3, 1000, 70_000));
+        assert!(probe.set_x(5));
+        assert_eq!((probe.kind(), probe.x(), probe.y()), (3, 5, 70_000));
+        assert!(probe.set("kind", 9));
+        assert_eq!(probe.get("kind"), Some(9));
+        assert!(probe.set_y((1 << 20) - 1));
+        assert_eq!(probe.y(), (1 << 20) - 1);
+        assert_eq!(probe.x(), 5);
+    }
+
+    #[test]
+    fn fields_and_widths_follow_the_declaration() {
+        assert_eq!(Probe::FIELDS, &["kind", "x", "y"]);
+        assert_eq!(Probe::WIDTHS, &[4, 20, 20]);
+        assert_eq!(Probe::cell_of("y"), Some(2));
+        assert_eq!(Probe::cell_of("z"), None);
+        assert_eq!(Probe::cell_map().cells(), 3);
+    }
+
+    #[test]
+    fn is_a_num_id() {
+        let probe = Probe::new(3, 1000, 70_000).unwrap();
+        let raw = 3 | 1000 << 4 | 70_000 << 24;
+        assert_eq!(probe.raw_id(), raw);
+        assert_eq!(probe.raw_facet(0), raw);
+        assert!(!probe.has_facets());
+        assert!(probe.same_alg(NumID::default().alg()));
+        assert_eq!(Probe::new_0_facet_id(raw), probe);
+        assert_eq!(NumID::from(probe.clone()), NumID::new(raw));
+        assert_eq!(serde_json::to_string(&probe).unwrap(), serde_json::to_string(&NumID::new(raw)).unwrap());
+    }
+
+    #[test]
+    fn values_too_wide_are_rejected() {
+        assert_eq!(Probe::new(16, 0, 0), None);
+        assert_eq!(Probe::new(0, 1 << 20, 0), None);
+        let mut probe = Probe::new(15, 1, 2).unwrap();
+        assert!(!probe.set_kind(16));
+        assert!(!probe.set("y", 1 << 20));
+        assert_eq!((probe.kind(), probe.x(), probe.y()), (15, 1, 2));
+        assert!(!probe.set("z", 1));
+        assert_eq!(probe.get("z"), None);
+    }
+}
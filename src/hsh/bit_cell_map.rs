@@ -18,12 +18,51 @@
  *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
  */
 #![allow(unused)]
-use std::sync::Arc;
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    sync::Arc
+};
 
 const ONE: u64 = 1;
 const _64BIT: u8 = 64;
 const _64BIT_N1: u8 = _64BIT - 1;
 
+/// Why a BitCellMap could not be built
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BitCellErr {
+    /// more than 64 cells
+    TooManyCells(usize),
+    /// a cell wider than 64 bits
+    CellTooWide { cell: usize, bits: u8 },
+    /// all cells together wider than 64 bits
+    TooManyBits(u32),
+}
+
+impl Display for BitCellErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitCellErr::TooManyCells(cells) => write!(
+                f,
+                "The length of bitCell must be less than or equal to {}, not {}!",
+                _64BIT, cells
+            ),
+            BitCellErr::CellTooWide { cell, bits } => write!(
+                f,
+                "The value for a bitCell cannot be greater than {}, cell {} is {}!",
+                _64BIT, cell, bits
+            ),
+            BitCellErr::TooManyBits(bits) => write!(
+                f,
+                "The combined value for all bitCells cannot be greater than {}, not {}!",
+                _64BIT, bits
+            ),
+        }
+    }
+}
+
+impl Error for BitCellErr {}
+
 /// Defines one dimension of a hash that may have N dimensions
 #[allow(non_snake_case)]
 pub struct BitCellMap {
@@ -35,39 +74,54 @@ pub struct BitCellMap {
 }
 
 impl BitCellMap {
+    /// Panics on an invalid bit_cell, see try_new
     pub fn new(bit_cell: &Arc<Vec<u8>>) -> Self {
+        Self::try_new(bit_cell).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// For bit cells that come from data rather than code
+    pub fn try_new(bit_cell: &Arc<Vec<u8>>) -> Result<Self, BitCellErr> {
+        if bit_cell.len() > _64BIT as usize {
+            return Err(BitCellErr::TooManyCells(bit_cell.len()));
+        }
         let cells = bit_cell.len() as u8;
-        if cells > _64BIT {
-            panic!(
-                "The length of bitCell must be less than or equal to {}!",
-                _64BIT
-            );
+        if let Some((cell, bits)) = bit_cell.iter().enumerate().find(|(_, bits)| **bits > _64BIT) {
+            return Err(BitCellErr::CellTooWide { cell, bits: *bits });
+        }
+        let bits_sum: u32 = bit_cell.iter().map(|bits| *bits as u32).sum();
+        if bits_sum > _64BIT as u32 {
+            return Err(BitCellErr::TooManyBits(bits_sum));
         }
-        let mut bits_sum = 0;
-        bit_cell.iter().for_each(|cell| {
-            if *cell > _64BIT {
-                panic!("The value for a bitCell cannot be greater than {}!", _64BIT);
-            }
-            bits_sum += cell;
-            if bits_sum > _64BIT {
-                panic!(
-                    "The combined value for all bitCells cannot be greater than {}!",
-                    _64BIT
-                );
-            }
-        });
 
-        let cell_shift = vec![0; cells as usize];
-        let cell_mask = vec![0; cells as usize];
-        let cell_zero = vec![0; cells as usize];
+        let mut cell_shift = vec![0; cells as usize];
+        let mut cell_mask = vec![0; cells as usize];
+        let mut cell_zero = vec![0; cells as usize];
+        let mut shift = 0;
+        bit_cell.iter().enumerate().for_each(|(cell, bits)| {
+            let mask = if *bits == 0 { 0 } else { Self::solid_bit_mask(0, bits - 1) };
+            cell_shift[cell] = shift;
+            cell_mask[cell] = mask;
+            cell_zero[cell] = !mask.checked_shl(shift as u32).unwrap_or(0);
+            shift += bits;
+        });
 
-        BitCellMap {
+        Ok(BitCellMap {
             CELL: bit_cell.clone(),
             CELLSHIFT: cell_shift,
             CELLMASK: cell_mask,
             CELLZERO: cell_zero,
             Cells: cells,
-        }
+        })
+    }
+
+    /// the number of cells
+    pub fn cells(&self) -> u8 {
+        self.Cells
+    }
+
+    /// the bit width of every cell
+    pub fn widths(&self) -> &[u8] {
+        &self.CELL
     }
 
     pub fn bits_at_cell(&self, cell: u8) -> u8 {
@@ -80,43 +134,53 @@ impl BitCellMap {
 
     // used to calculate the size of combined bitmasks
     // for redefining bit cell mapping for planets, stars, etc.
+    // see id::space_id::SpaceID
     pub fn total_bits_at_cells(&self, cells: &[u8]) -> u8 {
         cells.iter().map(|&cell| self.bits_at_cell(cell)).sum()
     }
 
-    pub fn bit_shift_cell(&mut self, cell: u8) -> u8 {
-        if cell == 0 {
-            return 0;
-        }
+    pub fn bit_shift_cell(&self, cell: u8) -> u8 {
+        if self.Cells <= cell { 0 } else { self.CELLSHIFT[cell as usize] }
+    }
 
-        let mut shift = self.CELLSHIFT[cell as usize];
-        if shift == 0 {
-            let mut _c = cell;
-            while _c != 0 {
-                shift += self.CELL[_c as usize - 1];
-                _c -= 1;
-            }
-            self.CELLSHIFT[cell as usize] = shift;
-        }
-        shift
+    pub fn and_mask_cell(&self, cell: u8) -> u64 {
+        if self.Cells <= cell { 0 } else { self.CELLMASK[cell as usize] }
     }
 
-    pub fn and_mask_cell(&mut self, cell: u8) -> u64 {
-        let mut mask = self.CELLMASK[cell as usize];
-        if mask == 0 {
-            mask = Self::solid_bit_mask(0, self.CELL[cell as usize] - 1);
-            self.CELLMASK[cell as usize] = mask;
+    pub fn zero_mask_cell(&self, cell: u8) -> u64 {
+        if self.Cells <= cell { !0 } else { self.CELLZERO[cell as usize] }
+    }
+
+    /// Reads one cell of a raw id, 0 for a cell that does not exist
+    pub fn get(&self, raw: u64, cell: u8) -> u64 {
+        raw.checked_shr(self.bit_shift_cell(cell) as u32).unwrap_or(0) & self.and_mask_cell(cell)
+    }
+
+    /// Writes one cell of a raw id<br>
+    /// Returns None if the cell does not exist or the value overflows it
+    pub fn set(&self, raw: u64, cell: u8, value: u64) -> Option<u64> {
+        if self.Cells <= cell || value & !self.and_mask_cell(cell) != 0 {
+            return None;
         }
-        mask
+        let shifted = value.checked_shl(self.bit_shift_cell(cell) as u32).unwrap_or(0);
+        Some((raw & self.zero_mask_cell(cell)) | shifted)
     }
 
-    pub fn zero_mask_cell(&mut self, cell: u8) -> u64 {
-        let mut mask = self.CELLZERO[cell as usize];
-        if mask == 0 {
-            mask = !(self.and_mask_cell(cell) << self.bit_shift_cell(cell));
-            self.CELLZERO[cell as usize] = mask;
+    /// Writes every cell, values\[0\] into cell 0 and so on<br>
+    /// Returns None if there is not one value per cell or a value overflows its cell
+    pub fn pack(&self, values: &[u64]) -> Option<u64> {
+        if values.len() != self.Cells as usize {
+            return None;
         }
-        mask
+        values
+            .iter()
+            .enumerate()
+            .try_fold(0u64, |raw, (cell, value)| self.set(raw, cell as u8, *value))
+    }
+
+    /// Reads every cell
+    pub fn unpack(&self, raw: u64) -> Vec<u64> {
+        (0..self.Cells).map(|cell| self.get(raw, cell)).collect()
     }
 
     fn solid_bit_mask(first_on: u8, last_on: u8) -> u64 {
@@ -135,3 +199,75 @@ impl BitCellMap {
         max
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(widths: &[u8]) -> BitCellMap {
+        BitCellMap::new(&Arc::new(widths.to_vec()))
+    }
+
+    #[test]
+    fn a_full_64_bit_cell_holds_any_value() {
+        let full = map(&[64]);
+        assert_eq!(full.set(0, 0, u64::MAX), Some(u64::MAX));
+        assert_eq!(full.set(u64::MAX, 0, 5), Some(5));
+        assert_eq!(full.get(u64::MAX, 0), u64::MAX);
+        assert_eq!(full.zero_mask_cell(0), 0);
+        assert_eq!(full.unpack(0x0123_4567_89ab_cdef), vec![0x0123_4567_89ab_cdef]);
+    }
+
+    #[test]
+    fn a_zero_width_cell_only_holds_0() {
+        let cells = map(&[8, 0, 56]);
+        let raw = cells.pack(&[0xff, 0, 1]).unwrap();
+        assert_eq!(raw, 0x1ff);
+        assert_eq!(cells.get(raw, 1), 0);
+        assert_eq!(cells.set(raw, 1, 0), Some(raw));
+        assert_eq!(cells.set(raw, 1, 1), None);
+        assert_eq!(cells.unpack(raw), vec![0xff, 0, 1]);
+        let last = map(&[64, 0]);
+        assert_eq!(last.set(u64::MAX, 1, 0), Some(u64::MAX));
+        assert_eq!(last.get(u64::MAX, 1), 0);
+    }
+
+    #[test]
+    fn an_out_of_range_cell_reads_0_and_is_not_set() {
+        let cells = map(&[4, 4]);
+        assert_eq!(cells.get(u64::MAX, 2), 0);
+        assert_eq!(cells.set(0, 2, 0), None);
+        assert_eq!(cells.bits_at_cell(2), 0);
+        assert_eq!(cells.zero_mask_cell(2), !0);
+    }
+
+    #[test]
+    fn set_rejects_a_value_too_large_for_its_cell() {
+        let cells = map(&[4, 12]);
+        assert_eq!(cells.set(0, 0, 0xf), Some(0xf));
+        assert_eq!(cells.set(0, 0, 0x10), None);
+        assert_eq!(cells.set(0, 1, 0xfff), Some(0xfff0));
+        assert_eq!(cells.set(0, 1, 0x1000), None);
+        assert_eq!(cells.pack(&[0x10, 0]), None);
+    }
+
+    #[test]
+    fn pack_needs_one_value_per_cell() {
+        let cells = map(&[4, 4, 8]);
+        assert_eq!(cells.pack(&[1, 2, 3]), Some(0x0321));
+        assert_eq!(cells.pack(&[1, 2]), None);
+        assert_eq!(cells.pack(&[1, 2, 3, 4]), None);
+        assert_eq!(cells.pack(&[]), None);
+    }
+
+    #[test]
+    fn try_new_rejects_invalid_widths() {
+        let try_new = |widths: Vec<u8>| BitCellMap::try_new(&Arc::new(widths)).err();
+        assert_eq!(try_new(vec![32, 32, 1]), Some(BitCellErr::TooManyBits(65)));
+        assert_eq!(try_new(vec![60, 10]), Some(BitCellErr::TooManyBits(70)));
+        assert_eq!(try_new(vec![8, 65]), Some(BitCellErr::CellTooWide { cell: 1, bits: 65 }));
+        assert_eq!(try_new(vec![0; 65]), Some(BitCellErr::TooManyCells(65)));
+        assert_eq!(try_new(vec![32, 32]), None);
+        assert_eq!(try_new(vec![1; 64]), None);
+    }
+}
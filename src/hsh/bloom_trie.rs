@@ -0,0 +1,446 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{
+    hash_trie::IHashTrie,
+    id::num_id::INumID,
+    obj::sub_hsh_obj::ISubHshObj,
+    sub_hsh_alg::{IUseSubHshAlg, SubHshAlg}
+};
+use crate::prng::fast_prng::split_mix;
+use parking_lot::RwLock as ParkLock;
+use std::{
+    f64::consts::LN_2,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock
+    }
+};
+
+const MAX_PROBES: u32 = 16;
+
+/// Wraps any IHashTrie with a Bloom filter,
+/// so most lookups of missing ids never reach the trie.<br>
+/// The filter is keyed on the rawid alone, like the tries it fronts,
+/// so ids that share a rawid pass it alike and the trie decides.<br>
+/// Removed ids stay in the filter until rebuild(),
+/// they only raise the false positive rate.<br>
+/// Inserts wait while rebuild() or dispose() runs, lookups never do.
+pub struct BloomTrie<O, I, T>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+    T: IHashTrie<O, I>,
+{
+    trie: T,
+    bits: Vec<AtomicU64>,
+    probes: u32,
+    capacity: u64,
+    fp_rate: f64,
+    stale: AtomicU64,
+    /// read by inserts, written by rebuild and dispose,
+    /// so no insert sets its bits into a filter that is being reset
+    gate: ParkLock<()>,
+    _marker: PhantomData<(O, I)>,
+}
+
+impl<O, I, T> BloomTrie<O, I, T>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+    T: IHashTrie<O, I>,
+{
+    /// Sized for capacity objects at fp_rate false positives,
+    /// anything already in trie is added.<br>
+    /// Past capacity the false positive rate climbs.<br>
+    /// None unless fp_rate is in (0, 0.5]
+    pub fn new(trie: T, capacity: u64, fp_rate: f64) -> Option<Self> {
+        if !(0.0 < fp_rate && fp_rate <= 0.5) {
+            return None;
+        }
+        let capacity = capacity.max(1);
+        let bits = (-(capacity as f64) * fp_rate.ln() / (LN_2 * LN_2)).ceil() as u64;
+        let words = bits.div_ceil(64).max(1);
+        let probes = ((words * 64) as f64 / capacity as f64 * LN_2).round() as u32;
+        let bloom = BloomTrie {
+            trie,
+            bits: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            probes: probes.clamp(1, MAX_PROBES),
+            capacity,
+            fp_rate,
+            stale: AtomicU64::new(0),
+            gate: ParkLock::new(()),
+            _marker: Default::default(),
+        };
+        bloom.rebuild();
+        Some(bloom)
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.trie
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn fp_rate(&self) -> f64 {
+        self.fp_rate
+    }
+
+    pub fn bit_len(&self) -> u64 {
+        self.bits.len() as u64 * 64
+    }
+
+    pub fn probes(&self) -> u32 {
+        self.probes
+    }
+
+    /// Removals since the last rebuild
+    pub fn stale(&self) -> u64 {
+        self.stale.load(Ordering::Relaxed)
+    }
+
+    /// The false positive rate the filter has now, from how full it is
+    pub fn current_fp_rate(&self) -> f64 {
+        let set: u32 = self.bits.iter().map(|word| word.load(Ordering::Relaxed).count_ones()).sum();
+        (set as f64 / self.bit_len() as f64).powi(self.probes as i32)
+    }
+
+    /// Builds the filter again from what the trie holds, dropping removed ids.<br>
+    /// Each word is swapped in whole and keeps the bits of every stored id,
+    /// so a lookup meanwhile never misses.
+    pub fn rebuild(&self) {
+        let _gate = self.gate.write();
+        let mut fresh = vec![0u64; self.bits.len()];
+        self.trie.walk(&mut |raw_id, _| {
+            self.positions(Self::key(raw_id))
+                .for_each(|bit| fresh[(bit / 64) as usize] |= 1 << (bit % 64));
+        });
+        self.bits.iter().zip(fresh).for_each(|(word, fresh)| word.store(fresh, Ordering::Relaxed));
+        self.stale.store(0, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.bits.iter().for_each(|word| word.store(0, Ordering::Relaxed));
+        self.stale.store(0, Ordering::Relaxed);
+    }
+
+    fn key(raw_id: u64) -> u64 {
+        split_mix(raw_id)
+    }
+
+    /// Double hashing, the odd step visits distinct bits
+    fn positions(&self, key: u64) -> impl Iterator<Item = u64> + '_ {
+        let bit_len = self.bit_len();
+        let step = split_mix(key) | 1;
+        (0..self.probes as u64).map(move |probe| key.wrapping_add(probe.wrapping_mul(step)) % bit_len)
+    }
+
+    fn set(&self, key: u64) {
+        self.positions(key).for_each(|bit| {
+            self.bits[(bit / 64) as usize].fetch_or(1 << (bit % 64), Ordering::Relaxed);
+        });
+    }
+
+    /// false means surely not stored
+    fn may_hold(&self, key: u64) -> bool {
+        self.positions(key)
+            .all(|bit| self.bits[(bit / 64) as usize].load(Ordering::Relaxed) & (1 << (bit % 64)) != 0)
+    }
+
+    fn may_hold_id(&self, id: &I) -> bool {
+        self.may_hold(Self::key(id.raw_id()))
+    }
+}
+
+impl<O, I, T> IUseSubHshAlg for BloomTrie<O, I, T>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+    T: IHashTrie<O, I>,
+{
+    fn alg(&self) -> &Arc<SubHshAlg> {
+        self.trie.alg()
+    }
+}
+
+impl<O, I, T> IHashTrie<O, I> for BloomTrie<O, I, T>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+    T: IHashTrie<O, I>,
+{
+    fn faceted(&self) -> bool {
+        self.trie.faceted()
+    }
+
+    fn size(&self) -> u64 {
+        self.trie.size()
+    }
+
+    /// The filter is set first, so a racing get never misses the obj
+    fn insert(&self, obj: Arc<RwLock<O>>, replace: bool) -> Option<bool> {
+        let key = Self::key(obj.read().unwrap().id().read().unwrap().raw_id());
+        let _gate = self.gate.read();
+        self.set(key);
+        self.trie.insert(obj, replace)
+    }
+
+    fn add(&self, obj: Arc<RwLock<O>>) -> Option<bool> {
+        self.insert(obj, false)
+    }
+
+    fn get(&self, id: Arc<RwLock<I>>) -> Option<Arc<RwLock<O>>> {
+        if !self.may_hold_id(&id.read().unwrap()) {
+            return None;
+        }
+        self.trie.get(id)
+    }
+
+    fn get_raw(&self, raw_id: u64) -> Option<Arc<RwLock<O>>> {
+        if !self.may_hold(Self::key(raw_id)) {
+            return None;
+        }
+        self.trie.get_raw(raw_id)
+    }
+
+    fn get_by(&self, id: &I) -> Option<Arc<RwLock<O>>> {
+        if !self.may_hold_id(id) {
+            return None;
+        }
+        self.trie.get_by(id)
+    }
+
+    fn contains_raw(&self, raw_id: u64) -> bool {
+        if !self.may_hold(Self::key(raw_id)) {
+            return false;
+        }
+        self.trie.contains_raw(raw_id)
+    }
+
+    fn contains(&self, id: &I) -> bool {
+        self.may_hold_id(id) && self.trie.contains(id)
+    }
+
+    fn remove(&self, id: Arc<RwLock<I>>) -> bool {
+        self.remove_raw(id.read().unwrap().raw_id())
+    }
+
+    fn remove_raw(&self, raw_id: u64) -> bool {
+        let removed = self.trie.remove_raw(raw_id);
+        if removed {
+            self.stale.fetch_add(1, Ordering::Relaxed);
+        }
+        removed
+    }
+
+    fn walk(&self, visit: &mut dyn FnMut(u64, &Arc<RwLock<O>>)) {
+        self.trie.walk(visit)
+    }
+
+    /// Inserts wait until both are cleared, so none is left unfiltered
+    fn dispose(&self) {
+        let _gate = self.gate.write();
+        self.trie.dispose();
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsh::{
+        dash_trie::DashTrie,
+        hsh_plugin::EntityObj,
+        id::num_id::NumID,
+        obj::test_obj::{obj, Obj},
+        str::str_id::StrID
+    };
+    use bevy::ecs::entity::Entity;
+    use std::thread;
+
+    type Bloom = BloomTrie<Obj, NumID, DashTrie<Obj, NumID>>;
+
+    fn bloom(capacity: u64) -> Bloom {
+        BloomTrie::new(DashTrie::new(), capacity, 0.01).unwrap()
+    }
+
+    /// Counts which lookups reach the trie
+    struct SpyTrie {
+        trie: DashTrie<Obj, NumID>,
+        gets: AtomicU64,
+        get_bys: AtomicU64,
+        get_raws: AtomicU64,
+    }
+
+    impl SpyTrie {
+        fn new() -> Self {
+            SpyTrie {
+                trie: DashTrie::new(),
+                gets: AtomicU64::new(0),
+                get_bys: AtomicU64::new(0),
+                get_raws: AtomicU64::new(0),
+            }
+        }
+    }
+
+    impl IUseSubHshAlg for SpyTrie {
+        fn alg(&self) -> &Arc<SubHshAlg> {
+            self.trie.alg()
+        }
+    }
+
+    impl IHashTrie<Obj, NumID> for SpyTrie {
+        fn faceted(&self) -> bool {
+            self.trie.faceted()
+        }
+
+        fn size(&self) -> u64 {
+            self.trie.size()
+        }
+
+        fn insert(&self, obj: Arc<RwLock<Obj>>, replace: bool) -> Option<bool> {
+            self.trie.insert(obj, replace)
+        }
+
+        fn add(&self, obj: Arc<RwLock<Obj>>) -> Option<bool> {
+            self.trie.add(obj)
+        }
+
+        fn get(&self, id: Arc<RwLock<NumID>>) -> Option<Arc<RwLock<Obj>>> {
+            self.gets.fetch_add(1, Ordering::Relaxed);
+            self.trie.get(id)
+        }
+
+        fn get_raw(&self, raw_id: u64) -> Option<Arc<RwLock<Obj>>> {
+            self.get_raws.fetch_add(1, Ordering::Relaxed);
+            self.trie.get_raw(raw_id)
+        }
+
+        fn get_by(&self, id: &NumID) -> Option<Arc<RwLock<Obj>>> {
+            self.get_bys.fetch_add(1, Ordering::Relaxed);
+            self.trie.get_by(id)
+        }
+
+        fn contains_raw(&self, raw_id: u64) -> bool {
+            self.trie.contains_raw(raw_id)
+        }
+
+        fn remove(&self, id: Arc<RwLock<NumID>>) -> bool {
+            self.trie.remove(id)
+        }
+
+        fn remove_raw(&self, raw_id: u64) -> bool {
+            self.trie.remove_raw(raw_id)
+        }
+
+        fn walk(&self, visit: &mut dyn FnMut(u64, &Arc<RwLock<Obj>>)) {
+            self.trie.walk(visit)
+        }
+
+        fn dispose(&self) {
+            self.trie.dispose()
+        }
+    }
+
+    #[test]
+    fn rejects_rates_outside_0_to_half() {
+        [0.0, -0.1, 0.6, f64::NAN, f64::INFINITY].into_iter().for_each(|fp_rate| {
+            assert!(BloomTrie::new(DashTrie::<Obj, NumID>::new(), 100, fp_rate).is_none(), "{fp_rate}");
+        });
+        assert!(BloomTrie::new(DashTrie::<Obj, NumID>::new(), 100, 0.5).is_some());
+    }
+
+    #[test]
+    fn lookups_reach_the_facet_aware_paths() {
+        let bloom = BloomTrie::new(SpyTrie::new(), 100, 0.01).unwrap();
+        bloom.add(obj(7));
+        assert!(bloom.get(Arc::new(RwLock::new(NumID::new(7)))).is_some());
+        assert!(bloom.get_by(&NumID::new(7)).is_some());
+        let spy = bloom.inner();
+        assert_eq!(spy.gets.load(Ordering::Relaxed), 1);
+        assert_eq!(spy.get_bys.load(Ordering::Relaxed), 1);
+        assert_eq!(spy.get_raws.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn filters_most_missing_ids() {
+        let bloom = bloom(10_000);
+        (1..=10_000).for_each(|raw| { bloom.add(obj(raw)); });
+        assert!((1..=10_000).all(|raw| bloom.contains_raw(raw)));
+        let passed = (10_001..=110_000).filter(|raw| bloom.may_hold(Bloom::key(*raw))).count();
+        assert!(passed < 2_000, "{passed} of 100000 passed");
+    }
+
+    #[test]
+    fn rebuild_never_hides_a_stored_id() {
+        let bloom = bloom(4_000);
+        thread::scope(|scope| {
+            scope.spawn(|| (0..50).for_each(|_| bloom.rebuild()));
+            (0..4u64).for_each(|thread| {
+                let bloom = &bloom;
+                scope.spawn(move || {
+                    (0..1_000).for_each(|at| {
+                        let raw = 1 + thread * 1_000 + at;
+                        bloom.add(obj(raw));
+                        assert!(bloom.get_by(&NumID::new(raw)).is_some(), "{raw} missed");
+                        if at % 3 == 0 {
+                            bloom.remove_raw(raw);
+                        }
+                    });
+                });
+            });
+        });
+        bloom.walk(&mut |raw_id, _| assert!(bloom.contains_raw(raw_id)));
+    }
+
+    #[test]
+    fn dispose_never_hides_a_later_insert() {
+        let bloom = bloom(4_000);
+        thread::scope(|scope| {
+            scope.spawn(|| (0..50).for_each(|_| bloom.dispose()));
+            (0..4u64).for_each(|thread| {
+                let bloom = &bloom;
+                scope.spawn(move || {
+                    (0..1_000).for_each(|at| {
+                        bloom.add(obj(1 + thread * 1_000 + at));
+                    });
+                });
+            });
+        });
+        bloom.walk(&mut |raw_id, _| assert!(bloom.contains_raw(raw_id), "{raw_id} missed"));
+    }
+
+    #[test]
+    fn faceted_ids_sharing_a_raw_id_pass_like_the_trie() {
+        let bloom = BloomTrie::new(DashTrie::<EntityObj<StrID>, StrID>::new(), 100, 0.01).unwrap();
+        let (red, big) = (StrID::with_raw_id("Red Knight", 7), StrID::with_raw_id("Big Knight", 7));
+        assert_ne!(red.raw_facet(1), big.raw_facet(1));
+        bloom.add(Arc::new(RwLock::new(EntityObj::new(red, Entity::PLACEHOLDER))));
+        assert!(bloom.get_by(&big).is_some() && bloom.inner().get_by(&big).is_some());
+        assert!(bloom.contains(&big));
+        assert!(bloom.contains_raw(7));
+        let passed = (8..10_008).filter(|raw| bloom.contains_raw(*raw)).count();
+        assert!(passed < 200, "{passed} of 10000 passed");
+    }
+}
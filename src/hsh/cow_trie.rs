@@ -0,0 +1,539 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{
+    hash_trie::IHashTrie,
+    id::num_id::INumID,
+    obj::sub_hsh_obj::ISubHshObj,
+    sub_hsh_alg::{ISubHshAlg, IUseSubHshAlg, SubHshAlg},
+    watch_trie::TrieDiff
+};
+use parking_lot::RwLock as ParkLock;
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, RwLock}
+};
+
+/// A directory holds only its used slots, the bitmap marks which.<br>
+/// u128 covers the largest directory, see sub_hsh_alg::MAX_BIT
+enum Node<O> {
+    Leaf { raw_id: u64, obj: Arc<RwLock<O>> },
+    Dir { bitmap: u128, kids: Vec<Arc<Node<O>>> },
+}
+
+impl<O> Node<O> {
+    fn empty() -> Arc<Self> {
+        Arc::new(Node::Dir { bitmap: 0, kids: vec![] })
+    }
+
+    fn leaf(raw_id: u64, obj: Arc<RwLock<O>>) -> Arc<Self> {
+        Arc::new(Node::Leaf { raw_id, obj })
+    }
+
+    fn walk(&self, visit: &mut dyn FnMut(u64, &Arc<RwLock<O>>)) {
+        match self {
+            Node::Leaf { raw_id, obj } => visit(*raw_id, obj),
+            Node::Dir { kids, .. } => kids.iter().for_each(|kid| kid.walk(visit)),
+        }
+    }
+
+    fn collect(&self) -> HashMap<u64, Arc<RwLock<O>>> {
+        let mut objs = HashMap::new();
+        self.walk(&mut |raw_id, obj| {
+            objs.insert(raw_id, obj.clone());
+        });
+        objs
+    }
+}
+
+/// the index of slot among the used slots of bitmap
+fn kid_index(bitmap: u128, slot: u8) -> usize {
+    (bitmap & ((1u128 << slot) - 1)).count_ones() as usize
+}
+
+struct Root<O> {
+    node: Arc<Node<O>>,
+    size: u64,
+}
+
+impl<O> Clone for Root<O> {
+    fn clone(&self) -> Self {
+        Root {
+            node: self.node.clone(),
+            size: self.size,
+        }
+    }
+}
+
+/// A persistent hash trie, one sub_hsh per depth.<br>
+/// A change copies only the path to the changed leaf and shares the rest,
+/// so snapshot() is O(1) and later changes never show in a snapshot.<br>
+/// Reads clone the root and walk without locks, changes are serialized.
+pub struct CowTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+{
+    root: ParkLock<Root<O>>,
+    sub_hsh_alg: Arc<SubHshAlg>,
+    max_depth: u8,
+    _marker: PhantomData<I>,
+}
+
+impl<O, I> CowTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+{
+    pub fn new() -> Self {
+        Self::from_root(
+            Root { node: Node::empty(), size: 0 },
+            I::default().alg().clone(),
+        )
+    }
+
+    /// SubHshAlg::check_bits refuses 0 bits, so bits divides 64 into depths
+    fn from_root(root: Root<O>, sub_hsh_alg: Arc<SubHshAlg>) -> Self {
+        let bits = sub_hsh_alg.dir_size().trailing_zeros() as u8;
+        debug_assert!(0 < bits, "a one slot directory never parts rawids");
+        CowTrie {
+            root: ParkLock::new(root),
+            max_depth: 64u8.div_ceil(bits),
+            sub_hsh_alg,
+            _marker: Default::default(),
+        }
+    }
+
+    /// A trie frozen as this one is now, sharing all of its nodes
+    pub fn snapshot(&self) -> Self {
+        Self::from_root(self.root.read().clone(), self.sub_hsh_alg.clone())
+    }
+
+    /// Makes this trie what snapshot was, for undo and rollback
+    pub fn restore(&self, snapshot: &Self) {
+        let root = snapshot.root.read().clone();
+        *self.root.write() = root;
+    }
+
+    /// The rawids that differ from a to b<br>
+    /// Shared subtrees are skipped, so the cost follows the changes
+    /// and not the size of the tries.
+    pub fn diff(a: &Self, b: &Self) -> TrieDiff {
+        let a_root = a.root.read().node.clone();
+        let b_root = b.root.read().node.clone();
+        let mut diff = TrieDiff::default();
+        Self::diff_nodes(Some(&a_root), Some(&b_root), &mut diff);
+        diff.added.sort_unstable();
+        diff.replaced.sort_unstable();
+        diff.removed.sort_unstable();
+        diff
+    }
+
+    fn diff_nodes(a: Option<&Arc<Node<O>>>, b: Option<&Arc<Node<O>>>, diff: &mut TrieDiff) {
+        match (a, b) {
+            (None, None) => {}
+            (Some(a), Some(b)) if Arc::ptr_eq(a, b) => {}
+            (Some(a), None) => a.walk(&mut |raw_id, _| diff.removed.push(raw_id)),
+            (None, Some(b)) => b.walk(&mut |raw_id, _| diff.added.push(raw_id)),
+            (Some(a), Some(b)) => match (a.as_ref(), b.as_ref()) {
+                (
+                    Node::Dir { bitmap: a_map, kids: a_kids },
+                    Node::Dir { bitmap: b_map, kids: b_kids },
+                ) => {
+                    (0..128u8)
+                        .filter(|slot| (a_map | b_map) & (1u128 << slot) != 0)
+                        .for_each(|slot| {
+                            let kid = |map: u128, kids: &[Arc<Node<O>>]| {
+                                (map & (1u128 << slot) != 0).then(|| kids[kid_index(map, slot)].clone())
+                            };
+                            Self::diff_nodes(
+                                kid(*a_map, a_kids).as_ref(),
+                                kid(*b_map, b_kids).as_ref(),
+                                diff,
+                            );
+                        });
+                }
+                // a leaf against a subtree, small enough to compare by rawid
+                _ => {
+                    let a_objs = a.collect();
+                    let b_objs = b.collect();
+                    b_objs.iter().for_each(|(raw_id, obj)| match a_objs.get(raw_id) {
+                        None => diff.added.push(*raw_id),
+                        Some(old) if !Arc::ptr_eq(old, obj) => diff.replaced.push(*raw_id),
+                        _ => {}
+                    });
+                    a_objs.keys()
+                        .filter(|raw_id| !b_objs.contains_key(raw_id))
+                        .for_each(|raw_id| diff.removed.push(*raw_id));
+                }
+            },
+        }
+    }
+
+    /// Returns the copied node, or None if nothing changed,
+    /// with the IHashTrie::insert result
+    fn insert_at(
+        &self,
+        node: &Arc<Node<O>>,
+        raw_id: u64,
+        obj: &Arc<RwLock<O>>,
+        depth: u8,
+        replace: bool,
+    ) -> (Option<Arc<Node<O>>>, Option<bool>) {
+        let Node::Dir { bitmap, kids } = node.as_ref() else {
+            unreachable!("insert_at is only called on directories");
+        };
+        let slot = self.sub_hsh_alg.sub_hsh(raw_id, depth);
+        let index = kid_index(*bitmap, slot);
+        if bitmap & (1u128 << slot) == 0 {
+            let mut kids = kids.clone();
+            kids.insert(index, Node::leaf(raw_id, obj.clone()));
+            let dir = Node::Dir { bitmap: bitmap | (1u128 << slot), kids };
+            return (Some(Arc::new(dir)), Some(true));
+        }
+        let (kid, stored) = match kids[index].as_ref() {
+            Node::Leaf { raw_id: leaf_id, .. } if *leaf_id == raw_id => {
+                if !replace {
+                    return (None, Some(false));
+                }
+                (Node::leaf(raw_id, obj.clone()), None)
+            }
+            Node::Leaf { raw_id: leaf_id, .. } => {
+                let split = self.split(kids[index].clone(), *leaf_id, raw_id, obj, depth + 1);
+                (split, Some(true))
+            }
+            Node::Dir { .. } => match self.insert_at(&kids[index], raw_id, obj, depth + 1, replace) {
+                (Some(kid), stored) => (kid, stored),
+                (None, stored) => return (None, stored),
+            },
+        };
+        let mut kids = kids.clone();
+        kids[index] = kid;
+        (Some(Arc::new(Node::Dir { bitmap: *bitmap, kids })), stored)
+    }
+
+    /// A directory at depth holding an existing leaf and a new one<br>
+    /// Two different rawids always part before max_depth.
+    fn split(
+        &self,
+        leaf: Arc<Node<O>>,
+        leaf_id: u64,
+        raw_id: u64,
+        obj: &Arc<RwLock<O>>,
+        depth: u8,
+    ) -> Arc<Node<O>> {
+        debug_assert!(depth < self.max_depth);
+        let leaf_slot = self.sub_hsh_alg.sub_hsh(leaf_id, depth);
+        let slot = self.sub_hsh_alg.sub_hsh(raw_id, depth);
+        if leaf_slot == slot {
+            let kid = self.split(leaf, leaf_id, raw_id, obj, depth + 1);
+            return Arc::new(Node::Dir { bitmap: 1u128 << slot, kids: vec![kid] });
+        }
+        let new_leaf = Node::leaf(raw_id, obj.clone());
+        let kids = if leaf_slot < slot { vec![leaf, new_leaf] } else { vec![new_leaf, leaf] };
+        Arc::new(Node::Dir {
+            bitmap: (1u128 << leaf_slot) | (1u128 << slot),
+            kids,
+        })
+    }
+
+    /// None if raw_id is not found, else the copied node,
+    /// which is None once a directory below the root empties.<br>
+    /// A directory left with a single leaf collapses into it.
+    fn remove_at(&self, node: &Arc<Node<O>>, raw_id: u64, depth: u8) -> Option<Option<Arc<Node<O>>>> {
+        let Node::Dir { bitmap, kids } = node.as_ref() else {
+            unreachable!("remove_at is only called on directories");
+        };
+        let slot = self.sub_hsh_alg.sub_hsh(raw_id, depth);
+        if bitmap & (1u128 << slot) == 0 {
+            return None;
+        }
+        let index = kid_index(*bitmap, slot);
+        let kid = match kids[index].as_ref() {
+            Node::Leaf { raw_id: leaf_id, .. } if *leaf_id == raw_id => None,
+            Node::Leaf { .. } => return None,
+            Node::Dir { .. } => self.remove_at(&kids[index], raw_id, depth + 1)?,
+        };
+        let mut kids = kids.clone();
+        let mut bitmap = *bitmap;
+        match kid {
+            Some(kid) => kids[index] = kid,
+            None => {
+                kids.remove(index);
+                bitmap &= !(1u128 << slot);
+            }
+        }
+        if 0 < depth {
+            if kids.is_empty() {
+                return Some(None);
+            }
+            if kids.len() == 1 && matches!(kids[0].as_ref(), Node::Leaf { .. }) {
+                return Some(kids.pop());
+            }
+        }
+        Some(Some(Arc::new(Node::Dir { bitmap, kids })))
+    }
+}
+
+impl<O, I> Default for CowTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<O, I> IUseSubHshAlg for CowTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+{
+    fn alg(&self) -> &Arc<SubHshAlg> {
+        &self.sub_hsh_alg
+    }
+}
+
+impl<O, I> IHashTrie<O, I> for CowTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+{
+    fn faceted(&self) -> bool {
+        false
+    }
+
+    fn size(&self) -> u64 {
+        self.root.read().size
+    }
+
+    fn insert(&self, obj: Arc<RwLock<O>>, replace: bool) -> Option<bool> {
+        let raw_id = obj.read().unwrap().id().read().unwrap().raw_id();
+        let mut root = self.root.write();
+        let (node, stored) = self.insert_at(&root.node, raw_id, &obj, 0, replace);
+        if let Some(node) = node {
+            root.node = node;
+        }
+        if stored == Some(true) {
+            root.size += 1;
+        }
+        stored
+    }
+
+    fn add(&self, obj: Arc<RwLock<O>>) -> Option<bool> {
+        self.insert(obj, false)
+    }
+
+    fn get(&self, id: Arc<RwLock<I>>) -> Option<Arc<RwLock<O>>> {
+        self.get_raw(id.read().unwrap().raw_id())
+    }
+
+    fn get_raw(&self, raw_id: u64) -> Option<Arc<RwLock<O>>> {
+        let mut node = self.root.read().node.clone();
+        let mut depth = 0;
+        loop {
+            let next = match node.as_ref() {
+                Node::Leaf { raw_id: leaf_id, obj } => {
+                    return (*leaf_id == raw_id).then(|| obj.clone());
+                }
+                Node::Dir { bitmap, kids } => {
+                    let slot = self.sub_hsh_alg.sub_hsh(raw_id, depth);
+                    if bitmap & (1u128 << slot) == 0 {
+                        return None;
+                    }
+                    kids[kid_index(*bitmap, slot)].clone()
+                }
+            };
+            node = next;
+            depth += 1;
+        }
+    }
+
+    fn remove(&self, id: Arc<RwLock<I>>) -> bool {
+        self.remove_raw(id.read().unwrap().raw_id())
+    }
+
+    fn remove_raw(&self, raw_id: u64) -> bool {
+        let mut root = self.root.write();
+        match self.remove_at(&root.node, raw_id, 0) {
+            Some(node) => {
+                root.node = node.unwrap_or_else(Node::empty);
+                root.size -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn walk(&self, visit: &mut dyn FnMut(u64, &Arc<RwLock<O>>)) {
+        let node = self.root.read().node.clone();
+        node.walk(visit);
+    }
+
+    fn dispose(&self) {
+        *self.root.write() = Root { node: Node::empty(), size: 0 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsh::{id::num_id::NumID, obj::test_obj::{obj, Obj}};
+
+    type Trie = CowTrie<Obj, NumID>;
+
+    fn trie(raw_ids: impl IntoIterator<Item = u64>) -> Trie {
+        let trie = Trie::new();
+        raw_ids.into_iter().for_each(|raw_id| {
+            trie.add(obj(raw_id));
+        });
+        trie
+    }
+
+    fn raw_ids(trie: &Trie) -> Vec<u64> {
+        let mut raw_ids = vec![];
+        trie.walk(&mut |raw_id, _| raw_ids.push(raw_id));
+        raw_ids.sort_unstable();
+        raw_ids
+    }
+
+    fn shares_root(a: &Trie, b: &Trie) -> bool {
+        Arc::ptr_eq(&a.root.read().node, &b.root.read().node)
+    }
+
+    /// the kid in slot of the root directory
+    fn root_kid(trie: &Trie, slot: u8) -> Arc<Node<Obj>> {
+        let root = trie.root.read().node.clone();
+        let Node::Dir { bitmap, kids } = root.as_ref() else {
+            panic!("the root is always a directory");
+        };
+        kids[kid_index(*bitmap, slot)].clone()
+    }
+
+    #[test]
+    fn snapshot_shares_the_root() {
+        let trie = trie(0..10_000);
+        let snapshot = trie.snapshot();
+        assert!(shares_root(&trie, &snapshot));
+        assert_eq!(snapshot.size(), 10_000);
+    }
+
+    #[test]
+    fn snapshot_ignores_later_changes() {
+        let trie = trie(0..100);
+        let snapshot = trie.snapshot();
+        let old = snapshot.get_raw(7).unwrap();
+        trie.add(obj(100));
+        trie.insert(obj(7), true);
+        trie.remove_raw(8);
+
+        assert_eq!(snapshot.size(), 100);
+        assert_eq!(raw_ids(&snapshot), (0..100).collect::<Vec<u64>>());
+        assert!(Arc::ptr_eq(&snapshot.get_raw(7).unwrap(), &old));
+        assert!(!Arc::ptr_eq(&trie.get_raw(7).unwrap(), &old));
+        assert!(snapshot.get_raw(8).is_some());
+        assert!(snapshot.get_raw(100).is_none());
+        assert_eq!(trie.size(), 100);
+    }
+
+    #[test]
+    fn diff_lists_added_replaced_and_removed() {
+        let trie = trie(0..100);
+        let before = trie.snapshot();
+        assert!(Trie::diff(&before, &trie).is_empty());
+
+        trie.add(obj(300));
+        trie.add(obj(200));
+        trie.insert(obj(7), true);
+        trie.remove_raw(8);
+        trie.remove_raw(0x11);
+
+        let diff = Trie::diff(&before, &trie);
+        assert_eq!(diff.added, vec![200, 300]);
+        assert_eq!(diff.replaced, vec![7]);
+        assert_eq!(diff.removed, vec![8, 0x11]);
+        let back = Trie::diff(&trie, &before);
+        assert_eq!(back.added, vec![8, 0x11]);
+        assert_eq!(back.replaced, vec![7]);
+        assert_eq!(back.removed, vec![200, 300]);
+    }
+
+    #[test]
+    fn diff_compares_a_leaf_against_a_subtree() {
+        let a = trie([0x01]);
+        let b = a.snapshot();
+        b.add(obj(0x11));
+        b.add(obj(0x21));
+        let diff = Trie::diff(&a, &b);
+        assert_eq!(diff.added, vec![0x11, 0x21]);
+        assert!(diff.replaced.is_empty() && diff.removed.is_empty());
+        assert_eq!(Trie::diff(&b, &a).removed, vec![0x11, 0x21]);
+    }
+
+    #[test]
+    fn restore_rolls_back() {
+        let trie = trie(0..100);
+        let snapshot = trie.snapshot();
+        trie.add(obj(100));
+        trie.remove_raw(8);
+        trie.insert(obj(7), true);
+
+        trie.restore(&snapshot);
+        assert!(shares_root(&trie, &snapshot));
+        assert_eq!(trie.size(), 100);
+        assert_eq!(raw_ids(&trie), (0..100).collect::<Vec<u64>>());
+        assert!(Arc::ptr_eq(&trie.get_raw(7).unwrap(), &snapshot.get_raw(7).unwrap()));
+    }
+
+    #[test]
+    fn remove_collapses_a_directory_left_with_one_leaf() {
+        // 0x001 and 0x101 share their first two sub hashes
+        let trie = trie([0x001, 0x101]);
+        assert!(matches!(root_kid(&trie, 1).as_ref(), Node::Dir { .. }));
+
+        assert!(trie.remove_raw(0x101));
+        assert!(matches!(root_kid(&trie, 1).as_ref(), Node::Leaf { raw_id: 0x001, .. }));
+        assert_eq!(trie.size(), 1);
+        assert!(trie.get_raw(0x001).is_some());
+        assert!(trie.get_raw(0x101).is_none());
+        assert!(!trie.remove_raw(0x101));
+
+        assert!(trie.remove_raw(0x001));
+        assert_eq!(trie.size(), 0);
+        assert!(raw_ids(&trie).is_empty());
+        trie.add(obj(0x101));
+        assert!(trie.get_raw(0x101).is_some());
+    }
+
+    #[test]
+    fn remove_keeps_a_directory_of_several_kids() {
+        let trie = trie([0x01, 0x11, 0x21]);
+        assert!(trie.remove_raw(0x11));
+        let kid = root_kid(&trie, 1);
+        let Node::Dir { kids, .. } = kid.as_ref() else {
+            panic!("two leaves stay in a directory");
+        };
+        assert_eq!(kids.len(), 2);
+        assert_eq!(raw_ids(&trie), vec![0x01, 0x21]);
+    }
+}
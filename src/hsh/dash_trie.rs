@@ -23,9 +23,14 @@ use crate::hsh::{
     hash_trie::IHashTrie,
     id::num_id::INumID,
     obj::sub_hsh_obj::ISubHshObj,
-    sub_hsh_alg::{IUseSubHshAlg, SubHshAlg}
+    sub_hsh_alg::{ISubHshAlg, IUseSubHshAlg, SubHshAlg}
+};
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::{
+    de::Error,
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer
 };
-use dashmap::DashMap;
 use std::{
     hash::Hash,
     marker::PhantomData,
@@ -70,11 +75,19 @@ where
 
     fn insert(&self, obj: Arc<RwLock<O>>, replace: bool)-> Option<bool> {
         let id = obj.read().unwrap().id().read().unwrap().raw_id();
-        if !replace && self.map.contains_key(&id) {
-            return Some(false);
+        match self.map.entry(id) {
+            Entry::Occupied(mut entry) => {
+                if !replace {
+                    return Some(false);
+                }
+                entry.insert(obj);
+                None
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(obj);
+                Some(true)
+            }
         }
-        self.map.insert(id, obj);
-        Some(true)
     }
 
     fn add(&self, obj: Arc<RwLock<O>>) -> Option<bool> {
@@ -82,21 +95,23 @@ where
     }
 
     fn get(&self, id: Arc<RwLock<I>>) -> Option<Arc<RwLock<O>>> {
-        let out = self.map.get(&id.read().unwrap().raw_id());
-        if out.is_some() { 
-            Some(out.unwrap().value().clone())
-        } else {
-            None
-        }
+        self.get_raw(id.read().unwrap().raw_id())
+    }
+
+    fn get_raw(&self, raw_id: u64) -> Option<Arc<RwLock<O>>> {
+        self.map.get(&raw_id).map(|out| out.value().clone())
     }
 
     fn remove(&self, id: Arc<RwLock<I>>) -> bool {
-        let ret = self.map.remove(&id.read().unwrap().raw_id());
-        if ret.is_some() {
-            true
-        } else {
-            false
-        }
+        self.remove_raw(id.read().unwrap().raw_id())
+    }
+
+    fn remove_raw(&self, raw_id: u64) -> bool {
+        self.map.remove(&raw_id).is_some()
+    }
+
+    fn walk(&self, visit: &mut dyn FnMut(u64, &Arc<RwLock<O>>)) {
+        self.map.iter().for_each(|entry| visit(*entry.key(), entry.value()));
     }
 
     fn dispose(&self) {
@@ -104,7 +119,17 @@ where
     }
 }
 
-impl<'a, O, I> DashTrie<O, I>
+impl<O, I> Default for DashTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default + Eq + Hash,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<O, I> DashTrie<O, I>
 where
     O: ISubHshObj<I>,
     I: INumID + Default + Eq + Hash,
@@ -112,8 +137,103 @@ where
     pub fn new() -> Self {
         DashTrie {
             map: DashMap::new(),
-            sub_hsh_alg: I::new_0_facet_id(0).alg().clone(),
+            sub_hsh_alg: I::default().alg().clone(),
             _marker: Default::default(),
         }
     }
 }
+
+/// Persists the alg by name and the stored objects ordered by rawid
+impl<O, I> Serialize for DashTrie<O, I>
+where
+    O: ISubHshObj<I> + Serialize,
+    I: INumID + Default + Eq + Hash,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries: Vec<(u64, Arc<RwLock<O>>)> = self.map
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect();
+        entries.sort_by_key(|(raw, _)| *raw);
+        let objs: Vec<Arc<RwLock<O>>> = entries.into_iter().map(|(_, obj)| obj).collect();
+
+        let mut trie = serializer.serialize_struct("DashTrie", 2)?;
+        trie.serialize_field("alg", self.sub_hsh_alg.name())?;
+        trie.serialize_field("objs", &objs)?;
+        trie.end()
+    }
+}
+
+/// Reattaches to the alg of I, failing if the persisted alg name differs
+/// or two persisted objects share a rawid
+impl<'de, O, I> Deserialize<'de> for DashTrie<O, I>
+where
+    O: ISubHshObj<I> + Deserialize<'de>,
+    I: INumID + Default + Eq + Hash,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct DashTrieDe<O> {
+            alg: String,
+            objs: Vec<O>,
+        }
+        let de = DashTrieDe::<O>::deserialize(deserializer)?;
+        let trie = Self::new();
+        trie.sub_hsh_alg.expect_name(&de.alg).map_err(D::Error::custom)?;
+        for obj in de.objs {
+            let raw = obj.id().read().unwrap().raw_id();
+            if trie.add(Arc::new(RwLock::new(obj))) != Some(true) {
+                return Err(D::Error::custom(format!("DashTrie holds rawid {} twice!", raw)));
+            }
+        }
+        Ok(trie)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsh::{
+        id::num_id::NumID,
+        obj::test_obj::{obj, Obj},
+        str::{str_id::StrID, str_obj::StrObj}
+    };
+
+    #[test]
+    fn round_trips_through_json() {
+        let trie: DashTrie<Obj, NumID> = DashTrie::new();
+        [3, 1, 2].into_iter().for_each(|raw| { trie.add(obj(raw)); });
+        let json = serde_json::to_string(&trie).unwrap();
+        assert_eq!(
+            json,
+            r#"{"alg":"num","objs":[{"id":{"raw":1,"alg":"num"}},{"id":{"raw":2,"alg":"num"}},{"id":{"raw":3,"alg":"num"}}]}"#
+        );
+        let loaded: DashTrie<Obj, NumID> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.size(), 3);
+        assert!((1..=3).all(|raw| loaded.contains_raw(raw)));
+    }
+
+    #[test]
+    fn str_objs_round_trip() {
+        let trie: DashTrie<StrObj<StrID>, StrID> = DashTrie::new();
+        ["a", "b"].into_iter().for_each(|str| { trie.add(Arc::new(RwLock::new(StrObj::new(str)))); });
+        let json = serde_json::to_string(&trie).unwrap();
+        let loaded: DashTrie<StrObj<StrID>, StrID> = serde_json::from_str(&json).unwrap();
+        assert!(loaded.contains(&StrID::new("a")) && loaded.contains(&StrID::new("b")));
+    }
+
+    #[test]
+    fn rejects_an_unknown_alg() {
+        let json = r#"{"alg":"nope","objs":[]}"#;
+        assert!(serde_json::from_str::<DashTrie<Obj, NumID>>(json).is_err());
+        let json = r#"{"alg":"num","objs":[{"id":{"raw":1,"alg":"str"}}]}"#;
+        assert!(serde_json::from_str::<DashTrie<Obj, NumID>>(json).is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_raw_ids() {
+        let json = r#"{"alg":"num","objs":[{"id":{"raw":1,"alg":"num"}},{"id":{"raw":1,"alg":"num"}}]}"#;
+        let err = serde_json::from_str::<DashTrie<Obj, NumID>>(json).err().unwrap();
+        assert!(err.to_string().contains("rawid 1 twice"), "{err}");
+    }
+}
@@ -50,9 +50,35 @@ where O: ISubHshObj<I>,
     
     /// Will find any stored object who's rawid is NOT zero!
     fn get(&self, id: Arc<RwLock<I>>) -> Option<Arc<RwLock<O>>>;
+
+    /// get without locking an id, the fast path for hot loops
+    fn get_raw(&self, raw_id: u64) -> Option<Arc<RwLock<O>>>;
+
+    fn get_by(&self, id: &I) -> Option<Arc<RwLock<O>>> {
+        self.get_raw(id.raw_id())
+    }
+
+    fn contains_raw(&self, raw_id: u64) -> bool {
+        self.get_raw(raw_id).is_some()
+    }
+
+    fn contains(&self, id: &I) -> bool {
+        self.contains_raw(id.raw_id())
+    }
     
     /// returns false if not found
     fn remove(&self, id: Arc<RwLock<I>>) -> bool;
+
+    /// remove without locking an id
+    fn remove_raw(&self, raw_id: u64) -> bool;
+
+    fn remove_by(&self, id: &I) -> bool {
+        self.remove_raw(id.raw_id())
+    }
+
+    /// Visits every stored object with its rawid, in no particular order<br>
+    /// visit must not insert into or remove from this trie
+    fn walk(&self, visit: &mut dyn FnMut(u64, &Arc<RwLock<O>>));
     
     fn dispose(&self);
 }
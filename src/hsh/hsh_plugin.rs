@@ -0,0 +1,320 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{
+    dash_trie::DashTrie,
+    hash_trie::IHashTrie,
+    id::num_id::{INumID, NumID},
+    obj::sub_hsh_obj::ISubHshObj,
+    register_algs,
+    str::str_id::StrID,
+    sub_hsh_alg::{IUseSubHshAlg, SubHshAlg}
+};
+use bevy::{
+    app::{App, Plugin},
+    ecs::{
+        component::{Component, ComponentHooks, ComponentId, StorageType},
+        entity::Entity,
+        system::Resource,
+        world::DeferredWorld
+    }
+};
+use std::{
+    hash::Hash,
+    sync::{Arc, RwLock}
+};
+
+/// Makes NumID and StrID usable as components.<br>
+/// Each id type gets an HshIndex resource from id to Entity,
+/// kept in sync by the component hooks.<br>
+/// The ids are registered for reflection, so moonshine-save
+/// persists them on Save entities and a load re-indexes them.
+pub struct HshPlugin;
+
+impl Plugin for HshPlugin {
+    fn build(&self, app: &mut App) {
+        register_algs();
+        app.register_type::<NumID>()
+            .register_type::<StrID>()
+            .init_resource::<HshIndex<NumID>>()
+            .init_resource::<HshIndex<StrID>>();
+    }
+}
+
+/// An id that can be a component and key an HshIndex
+pub trait IHshComponent: INumID + Component + Clone + Default + Eq + Hash {}
+
+impl<I> IHshComponent for I
+where I: INumID + Component + Clone + Default + Eq + Hash
+{}
+
+/// The Entity stored under an id in an HshIndex
+pub struct EntityObj<I>
+where
+    I: INumID + Default,
+{
+    id: Arc<RwLock<I>>,
+    alg: Arc<SubHshAlg>,
+    entity: Entity,
+}
+
+impl<I> EntityObj<I>
+where
+    I: INumID + Default,
+{
+    pub fn new(id: I, entity: Entity) -> Self {
+        let alg = id.alg().clone();
+        EntityObj {
+            id: Arc::new(RwLock::new(id)),
+            alg,
+            entity,
+        }
+    }
+
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+impl<I> IUseSubHshAlg for EntityObj<I>
+where
+    I: INumID + Default,
+{
+    fn alg(&self) -> &Arc<SubHshAlg> {
+        &self.alg
+    }
+}
+
+impl<I> ISubHshObj<I> for EntityObj<I>
+where
+    I: INumID + Default,
+{
+    fn id(&self) -> &Arc<RwLock<I>> {
+        &self.id
+    }
+}
+
+/// Finds the Entity that has an id component<br>
+/// If several entities share an id, or ids share a raw_id, the last inserted wins.
+#[derive(Resource)]
+pub struct HshIndex<I>
+where
+    I: IHshComponent,
+{
+    trie: DashTrie<EntityObj<I>, I>,
+}
+
+impl<I> HshIndex<I>
+where
+    I: IHshComponent,
+{
+    pub fn new() -> Self {
+        HshIndex {
+            trie: DashTrie::new(),
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.trie.size()
+    }
+
+    /// None if the entry under this raw_id belongs to another id
+    pub fn entity(&self, id: &I) -> Option<Entity> {
+        self.trie.get_raw(id.raw_id()).and_then(|obj| {
+            let obj = obj.read().unwrap();
+            (*obj.id().read().unwrap() == *id).then(|| obj.entity())
+        })
+    }
+
+    /// Whatever is indexed under raw_id, which may be a colliding id
+    pub fn entity_raw(&self, raw_id: u64) -> Option<Entity> {
+        self.trie
+            .get_raw(raw_id)
+            .map(|obj| obj.read().unwrap().entity())
+    }
+
+    pub fn trie(&self) -> &DashTrie<EntityObj<I>, I> {
+        &self.trie
+    }
+
+    fn index(&self, id: &I, entity: Entity) {
+        self.trie.insert(Arc::new(RwLock::new(EntityObj::new(id.clone(), entity))), true);
+    }
+
+    /// Only unindexes if the id still points at this entity
+    fn unindex(&self, id: &I, entity: Entity) {
+        if self.entity(id) == Some(entity) {
+            self.trie.remove_by(id);
+        }
+    }
+}
+
+impl<I> Default for HshIndex<I>
+where
+    I: IHshComponent,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// on_insert, so a component overwritten in place is indexed too
+fn index_on_insert<I: IHshComponent>(world: DeferredWorld, entity: Entity, _: ComponentId) {
+    let (Some(id), Some(index)) = (world.get::<I>(entity), world.get_resource::<HshIndex<I>>())
+    else { return; };
+    index.index(id, entity);
+}
+
+/// on_replace runs before both an overwrite and a removal,
+/// while the old id is still readable
+fn unindex_on_replace<I: IHshComponent>(world: DeferredWorld, entity: Entity, _: ComponentId) {
+    let (Some(id), Some(index)) = (world.get::<I>(entity), world.get_resource::<HshIndex<I>>())
+    else { return; };
+    index.unindex(id, entity);
+}
+
+impl Component for NumID {
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+
+    fn register_component_hooks(hooks: &mut ComponentHooks) {
+        hooks
+            .on_insert(index_on_insert::<Self>)
+            .on_replace(unindex_on_replace::<Self>);
+    }
+}
+
+impl Component for StrID {
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+
+    fn register_component_hooks(hooks: &mut ComponentHooks) {
+        hooks
+            .on_insert(index_on_insert::<Self>)
+            .on_replace(unindex_on_replace::<Self>);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{app::PreUpdate, MinimalPlugins};
+    use moonshine_save::prelude::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, HshPlugin));
+        app
+    }
+
+    fn num_index(app: &App) -> &HshIndex<NumID> {
+        app.world().resource::<HshIndex<NumID>>()
+    }
+
+    fn str_index(app: &App) -> &HshIndex<StrID> {
+        app.world().resource::<HshIndex<StrID>>()
+    }
+
+    #[test]
+    fn spawn_indexes_the_ids() {
+        let mut app = app();
+        let entity = app.world_mut().spawn((NumID::new(7), StrID::from("stone"))).id();
+        assert_eq!(num_index(&app).entity(&NumID::new(7)), Some(entity));
+        assert_eq!(str_index(&app).entity(&StrID::from("stone")), Some(entity));
+        assert_eq!(num_index(&app).size(), 1);
+        assert_eq!(str_index(&app).size(), 1);
+    }
+
+    #[test]
+    fn replacing_an_id_moves_the_entry() {
+        let mut app = app();
+        let entity = app.world_mut().spawn(NumID::new(7)).id();
+        app.world_mut().entity_mut(entity).insert(NumID::new(9));
+        assert_eq!(num_index(&app).entity(&NumID::new(7)), None);
+        assert_eq!(num_index(&app).entity(&NumID::new(9)), Some(entity));
+        assert_eq!(num_index(&app).size(), 1);
+    }
+
+    #[test]
+    fn despawn_unindexes_the_ids() {
+        let mut app = app();
+        let entity = app.world_mut().spawn((NumID::new(7), StrID::from("stone"))).id();
+        app.world_mut().despawn(entity);
+        assert_eq!(num_index(&app).entity(&NumID::new(7)), None);
+        assert_eq!(str_index(&app).entity(&StrID::from("stone")), None);
+        assert_eq!(num_index(&app).size(), 0);
+        assert_eq!(str_index(&app).size(), 0);
+    }
+
+    #[test]
+    fn a_shared_id_stays_with_the_last_entity() {
+        let mut app = app();
+        let first = app.world_mut().spawn(NumID::new(7)).id();
+        let last = app.world_mut().spawn(NumID::new(7)).id();
+        assert_eq!(num_index(&app).entity(&NumID::new(7)), Some(last));
+        app.world_mut().despawn(first);
+        assert_eq!(num_index(&app).entity(&NumID::new(7)), Some(last));
+    }
+
+    #[test]
+    fn colliding_ids_do_not_return_each_others_entity() {
+        let mut app = app();
+        let red = StrID::with_raw_id("Red Knight", 7);
+        let big = StrID::with_raw_id("Big Knight", 7);
+        assert_eq!(red.raw_id(), big.raw_id());
+        let red_entity = app.world_mut().spawn(red.clone()).id();
+        assert_eq!(str_index(&app).entity(&red), Some(red_entity));
+        assert_eq!(str_index(&app).entity(&big), None);
+        let big_entity = app.world_mut().spawn(big.clone()).id();
+        assert_eq!(str_index(&app).entity(&red), None);
+        assert_eq!(str_index(&app).entity(&big), Some(big_entity));
+        app.world_mut().despawn(red_entity);
+        assert_eq!(str_index(&app).entity(&big), Some(big_entity));
+        assert_eq!(str_index(&app).size(), 1);
+    }
+
+    #[test]
+    fn save_and_load_keep_and_reindex_the_ids() {
+        let path = std::env::temp_dir().join(format!("hsh_plugin_{}.ron", std::process::id()));
+
+        let mut saving = app();
+        saving.add_plugins(SavePlugin)
+            .add_systems(PreUpdate, save_default().into(static_file(path.clone())));
+        saving.world_mut().spawn((NumID::new(7), StrID::from("stone"), Save));
+        saving.world_mut().spawn((NumID::new(9), Save));
+        saving.update();
+
+        let mut loading = app();
+        loading.add_plugins(LoadPlugin)
+            .add_systems(PreUpdate, load(static_file(path.clone())));
+        loading.update();
+        std::fs::remove_file(&path).unwrap();
+
+        let stone = num_index(&loading).entity(&NumID::new(7)).unwrap();
+        assert_eq!(str_index(&loading).entity(&StrID::from("stone")), Some(stone));
+        let world = loading.world();
+        assert_eq!(world.get::<NumID>(stone), Some(&NumID::new(7)));
+        assert_eq!(world.get::<StrID>(stone), Some(&StrID::from("stone")));
+        assert!(world.get::<Save>(stone).is_some());
+        let nine = num_index(&loading).entity(&NumID::new(9)).unwrap();
+        assert_eq!(world.get::<NumID>(nine), Some(&NumID::new(9)));
+        assert_eq!(num_index(&loading).size(), 2);
+        assert_eq!(str_index(&loading).size(), 1);
+    }
+}
@@ -19,3 +19,5 @@
  */
 
 pub mod num_id;
+pub mod poly_id;
+pub mod space_id;
@@ -21,9 +21,14 @@
 
 use crate::hsh::{
     bit_cell_map::BitCellMap,
-    sub_hsh_alg::{IUseSubHshAlg, SubHshAlg}
+    sub_hsh_alg::{ISubHshAlg, IUseSubHshAlg, SubHshAlg}
+};
+use bevy::{
+    ecs::reflect::ReflectComponent,
+    reflect::{Reflect, ReflectDeserialize, ReflectSerialize}
 };
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use std::{
     clone::Clone,
     ops::Deref,
@@ -35,7 +40,7 @@ use std::sync::RwLock;
 
 static NUM_VEC: Lazy<Arc<Vec<u8>>> = Lazy::new(|| Arc::new(vec![64u8]));
 static NUM_MAP: Lazy<Arc<BitCellMap>> = Lazy::new(|| Arc::new(BitCellMap::new(NUM_VEC.deref())));
-static NUM_ALG: Lazy<Arc<SubHshAlg>> = Lazy::new(|| Arc::new(SubHshAlg::new(4, NUM_MAP.deref())));
+static NUM_ALG: Lazy<Arc<SubHshAlg>> = Lazy::new(|| SubHshAlg::register("num", 4, NUM_MAP.deref()));
 
 /// Traits for a simple single u64 hash id
 pub trait INumID: IUseSubHshAlg {
@@ -49,12 +54,42 @@ pub trait INumID: IUseSubHshAlg {
     where Self: Sized;
 }
 
-/// A simple single u64 hash id
+/// A simple single u64 hash id<br>
+/// Reflected as an opaque value so saved entities keep it, see HshPlugin
+#[derive(Serialize, Deserialize, Reflect)]
+#[serde(into = "NumIDSer", try_from = "NumIDSer")]
+#[reflect(opaque, Component, Serialize, Deserialize)]
 pub struct NumID {
     raw: u64,
     alg: Arc<SubHshAlg>,
 }
 
+/// Persisted form of a NumID, the alg is kept by name
+#[derive(Serialize, Deserialize)]
+struct NumIDSer {
+    raw: u64,
+    alg: String,
+}
+
+impl From<NumID> for NumIDSer {
+    fn from(id: NumID) -> Self {
+        NumIDSer {
+            raw: id.raw,
+            alg: id.alg.name().to_string(),
+        }
+    }
+}
+
+impl TryFrom<NumIDSer> for NumID {
+    type Error = String;
+
+    fn try_from(ser: NumIDSer) -> Result<Self, Self::Error> {
+        let id = NumID::new(ser.raw);
+        id.alg.expect_name(&ser.alg)?;
+        Ok(id)
+    }
+}
+
 impl NumID {
     pub fn new(raw_id: u64) -> Self {
         NumID {
@@ -138,6 +173,8 @@ pub trait IRawID: INumID {
     fn set_rawid(&mut self, rawid: u64);
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct RawID {
     num_id: NumID,
 }
@@ -198,3 +235,32 @@ impl RawID {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn num_id_round_trips_by_alg_name() {
+        let json = serde_json::to_string(&NumID::new(7)).unwrap();
+        assert_eq!(json, r#"{"raw":7,"alg":"num"}"#);
+        let id: NumID = serde_json::from_str(&json).unwrap();
+        assert_eq!(id, NumID::new(7));
+        assert!(id.same_alg(NumID::default().alg()));
+    }
+
+    #[test]
+    fn num_id_rejects_another_alg() {
+        assert!(serde_json::from_str::<NumID>(r#"{"raw":7,"alg":"str"}"#).is_err());
+        assert!(serde_json::from_str::<NumID>(r#"{"raw":7}"#).is_err());
+    }
+
+    #[test]
+    fn raw_id_is_transparent() {
+        let json = serde_json::to_string(&RawID::new_0_facet_id(9)).unwrap();
+        assert_eq!(json, r#"{"raw":9,"alg":"num"}"#);
+        let id: RawID = serde_json::from_str(&json).unwrap();
+        assert_eq!(id.raw_id(), 9);
+        assert!(serde_json::from_str::<RawID>(r#"{"raw":9,"alg":"poly"}"#).is_err());
+    }
+}
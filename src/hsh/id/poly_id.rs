@@ -0,0 +1,335 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{
+    bit_cell_map::BitCellMap,
+    id::num_id::{INumID, NumID},
+    sub_hsh_alg::{ISubHshAlg, IUseSubHshAlg, SubHshAlg}
+};
+use crate::prng::fast_prng::GOLDEN_GAMMA;
+use once_cell::sync::Lazy;
+use std::{
+    any::Any,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::{Arc, RwLock}
+};
+
+static POLY_VEC: Lazy<Arc<Vec<u8>>> = Lazy::new(|| Arc::new(vec![64u8]));
+static POLY_MAP: Lazy<Arc<BitCellMap>> = Lazy::new(|| Arc::new(BitCellMap::new(POLY_VEC.deref())));
+static POLY_ALG: Lazy<Arc<SubHshAlg>> = Lazy::new(|| SubHshAlg::register("poly", 4, POLY_MAP.deref()));
+
+
+/// A part of a PolyID, any id of any alg
+pub type PolyPart = Arc<RwLock<dyn INumID + Send + Sync>>;
+
+/// Traits for an id grouped from the ids of several algorithms
+pub trait IPolyID: INumID {
+    fn parts(&self) -> &[PolyPart];
+    /// the facet of one part, 0 if there is no such part
+    fn part_facet(&self, part: usize, facet: u8) -> u64;
+}
+
+/// Groups ids that never collide, each keeping its own SubHshAlg.<br>
+/// raw_id is the first part mixed with the rest,
+/// facet N above 0 is the raw_id of part N - 1.<br>
+/// Parts compare and hash by alg id and raw_id only, like a trie finds them,
+/// so parts that differ only in their facets above 0 are equal.<br>
+/// Clones share their parts.<br>
+/// Isomech's VennID, equal when any part is shared, is not provided:
+/// sharing a part is not transitive, so it cannot be Eq or Hash, nor a trie key.
+#[derive(Default)]
+pub struct PolyID {
+    parts: Vec<PolyPart>,
+}
+
+impl PolyID {
+    pub fn new(parts: Vec<PolyPart>) -> Self {
+        PolyID { parts }
+    }
+
+    /// Appends a part
+    pub fn with<I>(mut self, id: I) -> Self
+    where
+        I: INumID + Send + Sync + 'static,
+    {
+        self.parts.push(Arc::new(RwLock::new(id)));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Clones share parts, so a part may be compared with itself.<br>
+    /// Only one guard is held at a time, a std RwLock must not be read twice on one thread.
+    fn same_part(a: &PolyPart, b: &PolyPart) -> bool {
+        if Arc::ptr_eq(a, b) {
+            return true;
+        }
+        let key = |part: &PolyPart| {
+            let read_part = part.read().unwrap();
+            (read_part.alg().id(), read_part.raw_id())
+        };
+        key(a) == key(b)
+    }
+
+    fn same_parts(&self, other: &PolyID) -> bool {
+        self.parts.len() == other.parts.len()
+            && self.parts
+                .iter()
+                .zip(other.parts.iter())
+                .all(|(a, b)| Self::same_part(a, b))
+    }
+}
+
+impl PartialEq for PolyID {
+    fn eq(&self, other: &Self) -> bool {
+        self.same_parts(other)
+    }
+}
+
+impl Eq for PolyID {}
+
+impl Hash for PolyID {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.parts.iter().for_each(|part| {
+            let read_part = part.read().unwrap();
+            read_part.alg().id().hash(state);
+            read_part.raw_id().hash(state);
+        });
+    }
+}
+
+impl std::fmt::Debug for PolyID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<(String, u64)> = self.parts
+            .iter()
+            .map(|part| {
+                let read_part = part.read().unwrap();
+                (read_part.alg().name().to_string(), read_part.raw_id())
+            })
+            .collect();
+        f.debug_struct("PolyID")
+            .field("parts", &parts)
+            .finish()
+    }
+}
+
+impl Clone for PolyID {
+    fn clone(&self) -> Self {
+        PolyID { parts: self.parts.clone() }
+    }
+}
+
+impl IUseSubHshAlg for PolyID {
+    fn alg(&self) -> &Arc<SubHshAlg> {
+        POLY_ALG.deref()
+    }
+}
+
+impl INumID for PolyID {
+    fn as_any(&self) -> &(dyn Any) { self }
+
+    fn raw_id(&self) -> u64 {
+        let mut parts = self.parts.iter();
+        let mut raw = match parts.next() {
+            Some(part) => part.read().unwrap().raw_id(),
+            None => return 0,
+        };
+        parts.for_each(|part| {
+            raw = (raw.rotate_left(23) ^ part.read().unwrap().raw_id()).wrapping_mul(GOLDEN_GAMMA);
+        });
+        raw
+    }
+
+    fn raw_facet(&self, facet: u8) -> u64 {
+        if facet == 0 {
+            self.raw_id()
+        } else {
+            self.part_facet(facet as usize - 1, 0)
+        }
+    }
+
+    fn same_id(&self, id: &Arc<RwLock<dyn INumID>>) -> bool {
+        let read_id = id.read().unwrap();
+        if !read_id.same_alg(self.alg()) {
+            return false;
+        }
+        match read_id.as_any().downcast_ref::<PolyID>() {
+            Some(poly_id) => self.same_parts(poly_id),
+            None => false,
+        }
+    }
+
+    fn has_facets(&self) -> bool { true }
+
+    fn new_0_facet_id(raw: u64) -> Self {
+        PolyID::default().with(NumID::new(raw))
+    }
+}
+
+impl IPolyID for PolyID {
+    fn parts(&self) -> &[PolyPart] {
+        &self.parts
+    }
+
+    fn part_facet(&self, part: usize, facet: u8) -> u64 {
+        match self.parts.get(part) {
+            Some(part) => part.read().unwrap().raw_facet(facet),
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsh::{
+        dash_trie::DashTrie,
+        hash_trie::IHashTrie,
+        obj::sub_hsh_obj::ISubHshObj,
+        str::str_id::StrID
+    };
+
+    /// Stores a PolyID of any parts, SubHshObj only makes single part ones
+    struct PolyObj {
+        id: Arc<RwLock<PolyID>>,
+    }
+
+    impl IUseSubHshAlg for PolyObj {
+        fn alg(&self) -> &Arc<SubHshAlg> {
+            POLY_ALG.deref()
+        }
+    }
+
+    impl ISubHshObj<PolyID> for PolyObj {
+        fn id(&self) -> &Arc<RwLock<PolyID>> {
+            &self.id
+        }
+    }
+
+    fn poly_obj(id: PolyID) -> Arc<RwLock<PolyObj>> {
+        Arc::new(RwLock::new(PolyObj { id: Arc::new(RwLock::new(id)) }))
+    }
+
+    fn num_str(num: u64, str: &str) -> PolyID {
+        PolyID::default().with(NumID::new(num)).with(StrID::from(str))
+    }
+
+    #[test]
+    fn equals_its_clone() {
+        let poly = PolyID::default().with(NumID::new(7)).with(NumID::new(9));
+        assert_eq!(poly, poly.clone());
+    }
+
+    #[test]
+    fn parts_compare_by_alg_and_raw_id() {
+        let a = PolyID::default().with(NumID::new(7)).with(NumID::new(9));
+        let b = PolyID::default().with(NumID::new(7)).with(NumID::new(9));
+        let c = PolyID::default().with(NumID::new(9)).with(NumID::new(7));
+        let d = PolyID::default().with(NumID::new(7)).with(StrID::from("nine"));
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, d);
+    }
+
+    /// A NumID with a facet 1 of its own
+    struct Faceted {
+        raw: u64,
+        facet: u64,
+        alg: Arc<SubHshAlg>,
+    }
+
+    impl Faceted {
+        fn new(raw: u64, facet: u64) -> Self {
+            Faceted { raw, facet, alg: NumID::new(raw).alg().clone() }
+        }
+    }
+
+    impl IUseSubHshAlg for Faceted {
+        fn alg(&self) -> &Arc<SubHshAlg> {
+            &self.alg
+        }
+    }
+
+    impl INumID for Faceted {
+        fn as_any(&self) -> &(dyn Any) { self }
+        fn raw_id(&self) -> u64 { self.raw }
+        fn raw_facet(&self, facet: u8) -> u64 { if facet == 0 { self.raw } else { self.facet } }
+        fn same_id(&self, id: &Arc<RwLock<dyn INumID>>) -> bool { id.read().unwrap().raw_id() == self.raw }
+        fn has_facets(&self) -> bool { true }
+        fn new_0_facet_id(raw: u64) -> Self { Faceted::new(raw, 0) }
+    }
+
+    #[test]
+    fn parts_ignore_facets_above_0() {
+        let hash = |poly: &PolyID| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            poly.hash(&mut hasher);
+            hasher.finish()
+        };
+        let a = PolyID::default().with(NumID::new(7)).with(Faceted::new(9, 1));
+        let b = PolyID::default().with(NumID::new(7)).with(Faceted::new(9, 2));
+        assert_ne!(a.part_facet(1, 1), b.part_facet(1, 1));
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_eq!(a.raw_id(), b.raw_id());
+        assert_ne!(a, PolyID::default().with(NumID::new(7)).with(Faceted::new(8, 1)));
+    }
+
+    #[test]
+    fn raw_id_depends_on_part_order() {
+        let a = PolyID::default().with(NumID::new(7)).with(NumID::new(9));
+        let c = PolyID::default().with(NumID::new(9)).with(NumID::new(7));
+        assert_ne!(a.raw_id(), c.raw_id());
+        let abc = PolyID::default().with(NumID::new(1)).with(NumID::new(2)).with(NumID::new(3));
+        let bca = PolyID::default().with(NumID::new(2)).with(NumID::new(3)).with(NumID::new(1));
+        assert_ne!(abc.raw_id(), bca.raw_id());
+        assert_eq!(a.raw_facet(1), 7);
+        assert_eq!(c.raw_facet(1), 9);
+    }
+
+    #[test]
+    fn a_trie_finds_poly_ids_by_their_parts() {
+        let trie: DashTrie<PolyObj, PolyID> = DashTrie::new();
+        assert_eq!(trie.add(poly_obj(num_str(7, "nine"))), Some(true));
+        assert_eq!(trie.add(poly_obj(PolyID::default().with(NumID::new(7)).with(NumID::new(9)))), Some(true));
+        assert_eq!(trie.add(poly_obj(num_str(7, "nine"))), Some(false));
+        assert_eq!(trie.size(), 2);
+
+        // equal parts built again find the stored id
+        let found = trie.get_by(&num_str(7, "nine")).unwrap();
+        assert_eq!(*found.read().unwrap().id().read().unwrap(), num_str(7, "nine"));
+        assert!(trie.get(Arc::new(RwLock::new(num_str(7, "nine")))).is_some());
+        assert!(trie.get_by(&num_str(7, "ten")).is_none());
+        let reordered = PolyID::default().with(StrID::from("nine")).with(NumID::new(7));
+        assert!(trie.get_by(&reordered).is_none());
+
+        assert!(trie.remove_by(&num_str(7, "nine")));
+        assert!(!trie.contains(&num_str(7, "nine")));
+        assert_eq!(trie.size(), 1);
+    }
+}
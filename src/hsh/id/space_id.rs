@@ -0,0 +1,307 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{
+    bit_cell_layout::bit_cell_layout,
+    hash_trie::IHashTrie,
+    id::num_id::INumID,
+    obj::sub_hsh_obj::ISubHshObj
+};
+use std::sync::{Arc, RwLock};
+
+bit_cell_layout! {
+    /// Places a cell of a planet of a star of a galaxy in one u64
+    pub struct SpaceID {
+        galaxy, set_galaxy: 12,
+        star, set_star: 16,
+        planet, set_planet: 8,
+        cell, set_cell: 28,
+    }
+}
+
+/// The depth of a SpaceID, one level per field
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SpaceLevel {
+    Universe,
+    Galaxy,
+    Star,
+    Planet,
+    Cell,
+}
+
+impl SpaceLevel {
+    const ALL: [SpaceLevel; 5] = [
+        SpaceLevel::Universe,
+        SpaceLevel::Galaxy,
+        SpaceLevel::Star,
+        SpaceLevel::Planet,
+        SpaceLevel::Cell,
+    ];
+
+    fn from_depth(depth: u8) -> Self {
+        Self::ALL[depth as usize]
+    }
+
+    pub fn depth(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// A SpaceID is a node of the tree universe → galaxy → star → planet → cell.<br>
+/// A field of 0 is unset, so a node sets the fields down to its level
+/// and the universe singleton is rawid 0.<br>
+/// Many globes can share one hash space this way.
+impl SpaceID {
+    pub fn universe() -> Self {
+        Self::from_raw(0)
+    }
+
+    pub fn level(&self) -> SpaceLevel {
+        let depth = self.depth();
+        SpaceLevel::from_depth(depth)
+    }
+
+    /// false if a field is set below an unset one
+    pub fn is_valid(&self) -> bool {
+        let values = Self::cell_map().unpack(self.raw_id());
+        let depth = self.depth() as usize;
+        values[depth..].iter().all(|value| *value == 0)
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        let depth = self.depth();
+        if depth == 0 {
+            return None;
+        }
+        Self::cell_map()
+            .set(self.raw_id(), depth - 1, 0)
+            .map(Self::from_raw)
+    }
+
+    /// The nth child counting from 1, None at the cell level or if n overflows
+    pub fn child(&self, n: u64) -> Option<Self> {
+        let depth = self.depth();
+        if n == 0 || depth >= Self::cell_map().cells() {
+            return None;
+        }
+        Self::cell_map()
+            .set(self.raw_id(), depth, n)
+            .map(Self::from_raw)
+    }
+
+    /// Every possible child, lazily
+    pub fn children(&self) -> impl Iterator<Item = SpaceID> + '_ {
+        let depth = self.depth();
+        let last = Self::cell_map().and_mask_cell(depth);
+        (1..=last).filter_map(move |n| self.child(n))
+    }
+
+    /// The nth child of the parent, None for the universe
+    pub fn sibling(&self, n: u64) -> Option<Self> {
+        self.parent().and_then(|parent| parent.child(n))
+    }
+
+    /// Every node from the parent up to the universe
+    pub fn ancestors(&self) -> Vec<SpaceID> {
+        let mut ancestors = vec![];
+        let mut node = self.parent();
+        while let Some(parent) = node {
+            node = parent.parent();
+            ancestors.push(parent);
+        }
+        ancestors
+    }
+
+    /// true if other is below self, false for self itself
+    pub fn is_ancestor_of(&self, other: &SpaceID) -> bool {
+        let depth = self.depth();
+        depth < other.depth() && other.raw_id() & Self::level_mask(depth) == self.raw_id()
+    }
+
+    /// Walks a trie for every stored object below this node<br>
+    /// Visits every object in the trie, so it costs O(trie size) whatever the level.
+    /// Keep a trie per galaxy or planet when that matters.
+    pub fn descendants<O, I, T>(&self, trie: &T) -> Vec<Arc<RwLock<O>>>
+    where
+        T: IHashTrie<O, I> + ?Sized,
+        O: ISubHshObj<I>,
+        I: INumID + Default,
+    {
+        let depth = self.depth();
+        let mask = Self::level_mask(depth);
+        let raw = self.raw_id();
+        let mut found = vec![];
+        trie.walk(&mut |obj_raw, obj| {
+            if obj_raw != raw && obj_raw & mask == raw {
+                found.push(obj.clone());
+            }
+        });
+        found
+    }
+
+    /// the number of leading set fields
+    fn depth(&self) -> u8 {
+        let raw = self.raw_id();
+        let cells = Self::cell_map().cells();
+        (0..cells)
+            .find(|cell| Self::cell_map().get(raw, *cell) == 0)
+            .unwrap_or(cells)
+    }
+
+    /// the bits of every field above depth
+    fn level_mask(depth: u8) -> u64 {
+        let map = Self::cell_map();
+        if depth >= map.cells() {
+            return !0;
+        }
+        (1u64 << map.bit_shift_cell(depth)) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsh::{
+        dash_trie::DashTrie,
+        id::num_id::NumID,
+        obj::test_obj::{obj, Obj}
+    };
+
+    fn cell(galaxy: u64, star: u64, planet: u64, cell: u64) -> SpaceID {
+        SpaceID::new(galaxy, star, planet, cell).unwrap()
+    }
+
+    #[test]
+    fn levels_follow_the_set_fields() {
+        assert_eq!(SpaceID::universe().raw_id(), 0);
+        assert_eq!(SpaceID::universe().level(), SpaceLevel::Universe);
+        assert_eq!(cell(1, 0, 0, 0).level(), SpaceLevel::Galaxy);
+        assert_eq!(cell(1, 2, 0, 0).level(), SpaceLevel::Star);
+        assert_eq!(cell(1, 2, 3, 0).level(), SpaceLevel::Planet);
+        assert_eq!(cell(1, 2, 3, 4).level(), SpaceLevel::Cell);
+        assert_eq!(SpaceLevel::Cell.depth(), 4);
+    }
+
+    #[test]
+    fn fields_hold_12_16_8_and_28_bits() {
+        assert_eq!(SpaceID::WIDTHS, &[12, 16, 8, 28]);
+        let full = cell((1 << 12) - 1, (1 << 16) - 1, (1 << 8) - 1, (1 << 28) - 1);
+        assert_eq!(full.raw_id(), u64::MAX);
+        assert_eq!(full.level(), SpaceLevel::Cell);
+        assert!(SpaceID::new(1 << 12, 1, 1, 1).is_none());
+        assert!(SpaceID::new(1, 1 << 16, 1, 1).is_none());
+        assert!(SpaceID::new(1, 1, 1 << 8, 1).is_none());
+        assert!(SpaceID::new(1, 1, 1, 1 << 28).is_none());
+    }
+
+    #[test]
+    fn a_field_below_an_unset_one_is_invalid() {
+        assert!(SpaceID::universe().is_valid());
+        assert!(cell(1, 2, 0, 0).is_valid());
+        assert!(cell(1, 2, 3, 4).is_valid());
+        assert!(!cell(1, 0, 3, 0).is_valid());
+        assert!(!cell(0, 0, 0, 4).is_valid());
+    }
+
+    #[test]
+    fn parent_and_child_invert() {
+        let planet = cell(5, 6, 7, 0);
+        assert_eq!(planet.parent(), Some(cell(5, 6, 0, 0)));
+        assert_eq!(planet.child(9), Some(cell(5, 6, 7, 9)));
+        assert_eq!(planet.child(9).and_then(|child| child.parent()), Some(planet.clone()));
+        assert_eq!(SpaceID::universe().parent(), None);
+        assert_eq!(SpaceID::universe().child(1), Some(cell(1, 0, 0, 0)));
+    }
+
+    #[test]
+    fn child_is_refused_at_0_overflow_and_the_cell_level() {
+        let star = cell(5, 6, 0, 0);
+        assert_eq!(star.child(0), None);
+        assert_eq!(star.child((1 << 8) - 1), Some(cell(5, 6, 255, 0)));
+        assert_eq!(star.child(1 << 8), None);
+        assert_eq!(cell(1, 2, 3, 4).child(1), None);
+    }
+
+    #[test]
+    fn children_cover_the_field() {
+        let star = cell(5, 6, 0, 0);
+        let children: Vec<SpaceID> = star.children().collect();
+        assert_eq!(children.len(), 255);
+        assert_eq!(children.first(), Some(&cell(5, 6, 1, 0)));
+        assert_eq!(children.last(), Some(&cell(5, 6, 255, 0)));
+        assert!(children.iter().all(|child| child.parent() == Some(star.clone())));
+        assert_eq!(cell(1, 2, 3, 4).children().count(), 0);
+        assert_eq!(SpaceID::universe().children().count(), (1 << 12) - 1);
+    }
+
+    #[test]
+    fn siblings_share_a_parent() {
+        let planet = cell(5, 6, 7, 0);
+        assert_eq!(planet.sibling(8), Some(cell(5, 6, 8, 0)));
+        assert_eq!(planet.sibling(7), Some(planet.clone()));
+        assert_eq!(planet.sibling(1 << 8), None);
+        assert_eq!(SpaceID::universe().sibling(1), None);
+    }
+
+    #[test]
+    fn ancestors_climb_to_the_universe() {
+        assert_eq!(
+            cell(1, 2, 3, 4).ancestors(),
+            vec![cell(1, 2, 3, 0), cell(1, 2, 0, 0), cell(1, 0, 0, 0), SpaceID::universe()]
+        );
+        assert!(SpaceID::universe().ancestors().is_empty());
+    }
+
+    #[test]
+    fn is_ancestor_of_is_strict() {
+        let star = cell(1, 2, 0, 0);
+        assert!(SpaceID::universe().is_ancestor_of(&star));
+        assert!(cell(1, 0, 0, 0).is_ancestor_of(&star));
+        assert!(star.is_ancestor_of(&cell(1, 2, 3, 4)));
+        assert!(!star.is_ancestor_of(&star));
+        assert!(!star.is_ancestor_of(&cell(1, 0, 0, 0)));
+        assert!(!star.is_ancestor_of(&cell(1, 3, 3, 0)));
+        assert!(!star.is_ancestor_of(&cell(2, 2, 3, 0)));
+    }
+
+    #[test]
+    fn descendants_are_found_below_a_node_only() {
+        let trie: DashTrie<Obj, NumID> = DashTrie::new();
+        let star = cell(1, 2, 0, 0);
+        let below = [cell(1, 2, 3, 0), cell(1, 2, 3, 4), cell(1, 2, 9, 1)];
+        let outside = [cell(1, 0, 0, 0), cell(1, 3, 3, 0), cell(2, 2, 3, 4)];
+        below.iter().chain(outside.iter()).chain([&star]).for_each(|id| {
+            assert_eq!(trie.add(obj(id.raw_id())), Some(true));
+        });
+
+        let mut found: Vec<u64> = star
+            .descendants(&trie)
+            .iter()
+            .map(|obj| obj.read().unwrap().id().read().unwrap().raw_id())
+            .collect();
+        found.sort();
+        let mut expected: Vec<u64> = below.iter().map(|id| id.raw_id()).collect();
+        expected.sort();
+        assert_eq!(found, expected);
+        assert_eq!(SpaceID::universe().descendants(&trie).len(), 7);
+        assert!(cell(1, 2, 3, 4).descendants(&trie).is_empty());
+    }
+}
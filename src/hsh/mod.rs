@@ -18,10 +18,32 @@
  *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+#[cfg(any(test, feature = "fuzz"))]
+pub mod bit_cell_fuzz;
+pub mod bit_cell_layout;
 pub mod bit_cell_map;
+pub mod bloom_trie;
 pub mod id;
 pub mod obj;
 pub mod str;
 pub mod sub_hsh_alg;
 pub mod hash_trie;
+pub mod hsh_plugin;
+pub mod cow_trie;
 pub mod dash_trie;
+pub mod park_trie;
+pub mod watch_trie;
+
+use crate::hsh::{
+    id::{num_id::NumID, poly_id::PolyID},
+    str::str_id::StrID,
+    sub_hsh_alg::IUseSubHshAlg
+};
+
+/// Registers the built-in algorithms so SubHshAlg::by_name can find them
+/// before any of their ids have been made.
+pub fn register_algs() {
+    NumID::default().alg();
+    StrID::default().alg();
+    PolyID::default().alg();
+}
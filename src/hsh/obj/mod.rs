@@ -18,3 +18,6 @@
  *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
  */
 pub mod sub_hsh_obj;
+pub mod obj_registry;
+#[cfg(test)]
+pub mod test_obj;
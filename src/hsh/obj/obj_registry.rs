@@ -0,0 +1,361 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{
+    id::num_id::INumID,
+    obj::sub_hsh_obj::ISubHshObj,
+    sub_hsh_alg::{IUseSubHshAlg, SubHshAlg}
+};
+use bevy::prelude::{Entity, Resource};
+use dashmap::{mapref::entry::Entry, DashMap, DashSet};
+use std::{
+    any::{Any, TypeId},
+    sync::{Arc, RwLock}
+};
+
+/// Any stored object, whatever its type
+pub type DynObj<I> = Arc<RwLock<dyn ISubHshObj<I> + Send + Sync>>;
+
+struct RegEntry<I>
+where
+    I: INumID + Default,
+{
+    type_id: TypeId,
+    obj: DynObj<I>,
+    /// the Arc<RwLock<T>> the obj was stored as
+    typed: Arc<dyn Any + Send + Sync>,
+}
+
+/// Stores objects of many types that share one id space.<br>
+/// Objects downcast back to their stored type,
+/// each type keeps an index of its rawids,
+/// and an id can be bound to the Bevy Entity that represents it.
+#[derive(Resource)]
+pub struct ObjRegistry<I>
+where
+    I: INumID + Default + Send + Sync + 'static,
+{
+    objs: DashMap<u64, RegEntry<I>>,
+    types: DashMap<TypeId, DashSet<u64>>,
+    entities: DashMap<u64, Entity>,
+    sub_hsh_alg: Arc<SubHshAlg>,
+}
+
+impl<I> ObjRegistry<I>
+where
+    I: INumID + Default + Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        ObjRegistry {
+            objs: DashMap::new(),
+            types: DashMap::new(),
+            entities: DashMap::new(),
+            sub_hsh_alg: I::default().alg().clone(),
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.objs.len() as u64
+    }
+
+    /// Returns like IHashTrie::insert:<br>
+    /// Some(true) if obj is stored<br>
+    /// Some(false) if obj is already there<br>
+    /// None if obj is replaced, even by another type<br>
+    pub fn insert<T>(&self, obj: Arc<RwLock<T>>, replace: bool) -> Option<bool>
+    where
+        T: ISubHshObj<I> + Send + Sync + 'static,
+    {
+        let raw_id = obj.read().unwrap().id().read().unwrap().raw_id();
+        let type_id = TypeId::of::<T>();
+        let reg_entry = RegEntry {
+            type_id,
+            obj: obj.clone(),
+            typed: Arc::new(obj),
+        };
+        // types is kept in step while the objs entry is held,
+        // so a racing remove of the same rawid waits for both
+        match self.objs.entry(raw_id) {
+            Entry::Occupied(mut entry) => {
+                if !replace {
+                    return Some(false);
+                }
+                let old_type = entry.get().type_id;
+                if old_type != type_id {
+                    if let Some(ids) = self.types.get(&old_type) {
+                        ids.remove(&raw_id);
+                    }
+                    self.types.entry(type_id).or_default().insert(raw_id);
+                }
+                entry.insert(reg_entry);
+                None
+            }
+            Entry::Vacant(entry) => {
+                self.types.entry(type_id).or_default().insert(raw_id);
+                entry.insert(reg_entry);
+                Some(true)
+            }
+        }
+    }
+
+    pub fn add<T>(&self, obj: Arc<RwLock<T>>) -> Option<bool>
+    where
+        T: ISubHshObj<I> + Send + Sync + 'static,
+    {
+        self.insert(obj, false)
+    }
+
+    pub fn get(&self, id: &I) -> Option<DynObj<I>> {
+        self.objs.get(&id.raw_id()).map(|entry| entry.obj.clone())
+    }
+
+    /// None if nothing is stored or it is not a T
+    pub fn get_as<T>(&self, id: &I) -> Option<Arc<RwLock<T>>>
+    where
+        T: ISubHshObj<I> + Send + Sync + 'static,
+    {
+        self.objs
+            .get(&id.raw_id())
+            .and_then(|entry| entry.typed.downcast_ref::<Arc<RwLock<T>>>().cloned())
+    }
+
+    pub fn is<T: 'static>(&self, id: &I) -> bool {
+        self.type_of(id) == Some(TypeId::of::<T>())
+    }
+
+    pub fn type_of(&self, id: &I) -> Option<TypeId> {
+        self.objs.get(&id.raw_id()).map(|entry| entry.type_id)
+    }
+
+    /// The rawids of every stored T
+    pub fn ids_of<T: 'static>(&self) -> Vec<u64> {
+        match self.types.get(&TypeId::of::<T>()) {
+            Some(ids) => ids.iter().map(|raw_id| *raw_id).collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn count_of<T: 'static>(&self) -> usize {
+        self.types
+            .get(&TypeId::of::<T>())
+            .map(|ids| ids.len())
+            .unwrap_or_default()
+    }
+
+    /// Every stored T
+    pub fn all_of<T>(&self) -> Vec<Arc<RwLock<T>>>
+    where
+        T: ISubHshObj<I> + Send + Sync + 'static,
+    {
+        self.ids_of::<T>()
+            .into_iter()
+            .filter_map(|raw_id| {
+                self.objs
+                    .get(&raw_id)
+                    .and_then(|entry| entry.typed.downcast_ref::<Arc<RwLock<T>>>().cloned())
+            })
+            .collect()
+    }
+
+    /// Removes the object and its Entity binding<br>
+    /// returns false if not found
+    pub fn remove(&self, id: &I) -> bool {
+        let raw_id = id.raw_id();
+        self.entities.remove(&raw_id);
+        match self.objs.entry(raw_id) {
+            Entry::Occupied(entry) => {
+                if let Some(ids) = self.types.get(&entry.get().type_id) {
+                    ids.remove(&raw_id);
+                }
+                entry.remove();
+                true
+            }
+            Entry::Vacant(_) => false,
+        }
+    }
+
+    /// Binds an id to the Entity that represents it, returns the previous Entity
+    pub fn bind_entity(&self, id: &I, entity: Entity) -> Option<Entity> {
+        self.entities.insert(id.raw_id(), entity)
+    }
+
+    pub fn unbind_entity(&self, id: &I) -> Option<Entity> {
+        self.entities.remove(&id.raw_id()).map(|(_, entity)| entity)
+    }
+
+    pub fn entity(&self, id: &I) -> Option<Entity> {
+        self.entities.get(&id.raw_id()).map(|entity| *entity)
+    }
+
+    pub fn dispose(&self) {
+        self.objs.clear();
+        self.types.clear();
+        self.entities.clear();
+    }
+}
+
+impl<I> Default for ObjRegistry<I>
+where
+    I: INumID + Default + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> IUseSubHshAlg for ObjRegistry<I>
+where
+    I: INumID + Default + Send + Sync + 'static,
+{
+    fn alg(&self) -> &Arc<SubHshAlg> {
+        &self.sub_hsh_alg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsh::{id::num_id::NumID, obj::{sub_hsh_obj::SubHshObj, test_obj::{obj, Obj}}};
+    use std::thread;
+
+    /// A second type in the same id space
+    struct Tagged(Obj);
+
+    impl IUseSubHshAlg for Tagged {
+        fn alg(&self) -> &Arc<SubHshAlg> {
+            self.0.alg()
+        }
+    }
+
+    impl ISubHshObj<NumID> for Tagged {
+        fn id(&self) -> &Arc<RwLock<NumID>> {
+            self.0.id()
+        }
+    }
+
+    fn tagged(raw: u64) -> Arc<RwLock<Tagged>> {
+        Arc::new(RwLock::new(Tagged(Obj::new(raw))))
+    }
+
+    #[test]
+    fn downcasts_to_the_stored_type_only() {
+        let registry: ObjRegistry<NumID> = ObjRegistry::new();
+        let (one, two) = (obj(1), tagged(2));
+        registry.add(one.clone());
+        registry.add(two.clone());
+        assert_eq!(registry.size(), 2);
+        assert!(Arc::ptr_eq(&registry.get_as::<Obj>(&NumID::new(1)).unwrap(), &one));
+        assert!(Arc::ptr_eq(&registry.get_as::<Tagged>(&NumID::new(2)).unwrap(), &two));
+        assert!(registry.get_as::<Tagged>(&NumID::new(1)).is_none());
+        assert!(registry.get_as::<Obj>(&NumID::new(2)).is_none());
+        assert!(registry.get_as::<Obj>(&NumID::new(3)).is_none());
+        assert_eq!(registry.get(&NumID::new(2)).unwrap().read().unwrap().id().read().unwrap().raw_id(), 2);
+        assert!(registry.get(&NumID::new(3)).is_none());
+        assert_eq!(registry.type_of(&NumID::new(1)), Some(TypeId::of::<Obj>()));
+        assert_eq!(registry.type_of(&NumID::new(3)), None);
+        assert!(registry.is::<Obj>(&NumID::new(1)) && !registry.is::<Tagged>(&NumID::new(1)));
+    }
+
+    #[test]
+    fn all_of_returns_every_stored_t() {
+        let registry: ObjRegistry<NumID> = ObjRegistry::new();
+        let objs: Vec<_> = (1..=5).map(obj).collect();
+        objs.iter().for_each(|one| { registry.add(one.clone()); });
+        (6..=8).for_each(|raw| { registry.add(tagged(raw)); });
+        registry.remove(&NumID::new(3));
+
+        let mut ids = registry.ids_of::<Obj>();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 2, 4, 5]);
+        let all = registry.all_of::<Obj>();
+        assert_eq!(all.len(), 4);
+        assert!(all.iter().all(|one| objs.iter().any(|stored| Arc::ptr_eq(one, stored))));
+        assert_eq!((registry.count_of::<Tagged>(), registry.all_of::<Tagged>().len()), (3, 3));
+
+        struct Unstored;
+        assert!(registry.ids_of::<Unstored>().is_empty() && registry.count_of::<Unstored>() == 0);
+    }
+
+    #[test]
+    fn entities_bind_until_unbound_or_removed() {
+        let registry: ObjRegistry<NumID> = ObjRegistry::new();
+        let (first, second) = (Entity::from_raw(7), Entity::from_raw(8));
+        registry.add(obj(1));
+        assert_eq!(registry.entity(&NumID::new(1)), None);
+        assert_eq!(registry.bind_entity(&NumID::new(1), first), None);
+        assert_eq!(registry.entity(&NumID::new(1)), Some(first));
+        assert_eq!(registry.bind_entity(&NumID::new(1), second), Some(first));
+        assert_eq!(registry.unbind_entity(&NumID::new(1)), Some(second));
+        assert_eq!(registry.unbind_entity(&NumID::new(1)), None);
+        assert_eq!(registry.entity(&NumID::new(1)), None);
+
+        registry.bind_entity(&NumID::new(1), first);
+        assert!(registry.remove(&NumID::new(1)));
+        assert!(!registry.remove(&NumID::new(1)));
+        assert_eq!(registry.entity(&NumID::new(1)), None);
+
+        registry.add(obj(2));
+        registry.bind_entity(&NumID::new(2), first);
+        registry.dispose();
+        assert_eq!((registry.size(), registry.count_of::<Obj>()), (0, 0));
+        assert_eq!(registry.entity(&NumID::new(2)), None);
+    }
+
+    #[test]
+    fn replacing_moves_the_id_between_types() {
+        let registry: ObjRegistry<NumID> = ObjRegistry::new();
+        assert_eq!(registry.add(obj(1)), Some(true));
+        assert_eq!(registry.add(tagged(1)), Some(false));
+        assert_eq!(registry.insert(tagged(1), true), None);
+        assert!(registry.is::<Tagged>(&NumID::new(1)));
+        assert_eq!((registry.count_of::<Obj>(), registry.count_of::<Tagged>()), (0, 1));
+        assert!(registry.get_as::<Obj>(&NumID::new(1)).is_none());
+        assert!(registry.remove(&NumID::new(1)));
+        assert_eq!(registry.count_of::<Tagged>(), 0);
+    }
+
+    #[test]
+    fn type_index_matches_objs_under_races() {
+        let registry: ObjRegistry<NumID> = ObjRegistry::new();
+        thread::scope(|scope| {
+            (0..4).for_each(|thread| {
+                let registry = &registry;
+                scope.spawn(move || {
+                    (0..2_000u64).for_each(|round| {
+                        let raw = 1 + round % 64;
+                        match (round + thread) % 3 {
+                            0 => registry.insert(obj(raw), true).is_some(),
+                            1 => registry.insert(tagged(raw), true).is_some(),
+                            _ => registry.remove(&NumID::new(raw)),
+                        };
+                    });
+                });
+            });
+        });
+        let mut ids = registry.ids_of::<Obj>();
+        ids.extend(registry.ids_of::<Tagged>());
+        ids.sort_unstable();
+        let mut stored: Vec<u64> = registry.objs.iter().map(|entry| *entry.key()).collect();
+        stored.sort_unstable();
+        assert_eq!(ids, stored);
+        assert!(registry.ids_of::<Obj>().iter().all(|raw| registry.is::<Obj>(&NumID::new(*raw))));
+    }
+}
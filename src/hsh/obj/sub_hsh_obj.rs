@@ -21,6 +21,10 @@
 
 use crate::hsh::id::num_id::INumID;
 use crate::hsh::sub_hsh_alg::{IUseSubHshAlg, SubHshAlg};
+use serde::{
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer
+};
 use std::sync::{Arc, RwLock};
 use crate::hsh::hash_trie::MAX_FACETS;
 
@@ -88,4 +92,34 @@ where
     fn id(&self) -> &Arc<RwLock<I>> {
         &self.id
     }
-}
\ No newline at end of file
+}
+
+/// Only the id is persisted, the alg is reattached from the loaded id
+impl<I> Serialize for SubHshObj<I>
+where
+    I: INumID + Clone + Default + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut obj = serializer.serialize_struct("SubHshObj", 1)?;
+        obj.serialize_field("id", &self.id)?;
+        obj.end()
+    }
+}
+
+impl<'de, I> Deserialize<'de> for SubHshObj<I>
+where
+    I: INumID + Clone + Default + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct SubHshObjDe<I> {
+            id: I,
+        }
+        let de = SubHshObjDe::<I>::deserialize(deserializer)?;
+        let alg = de.id.alg().clone();
+        Ok(Self {
+            id: Arc::new(RwLock::new(de.id)),
+            alg,
+        })
+    }
+}
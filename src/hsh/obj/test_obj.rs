@@ -0,0 +1,30 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{id::num_id::NumID, obj::sub_hsh_obj::SubHshObj};
+use std::sync::{Arc, RwLock};
+
+/// The obj trie tests store
+pub type Obj = SubHshObj<NumID>;
+
+pub fn obj(raw: u64) -> Arc<RwLock<Obj>> {
+    Arc::new(RwLock::new(Obj::new(raw)))
+}
@@ -0,0 +1,258 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{
+    hash_trie::IHashTrie,
+    id::num_id::INumID,
+    obj::sub_hsh_obj::ISubHshObj,
+    sub_hsh_alg::{ISubHshAlg, IUseSubHshAlg, SubHshAlg}
+};
+use crate::prng::fast_prng::{split_mix, GOLDEN_GAMMA};
+use parking_lot::RwLock as ParkLock;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    hash::{BuildHasherDefault, Hasher},
+    marker::PhantomData,
+    sync::{Arc, RwLock}
+};
+
+const MAX_SHARDS: usize = 1 << 16;
+
+/// A split_mix of the rawid: hashbrown picks buckets from the low bits,
+/// and rawids like SpaceIDs differ only in their high ones
+#[derive(Default)]
+pub struct RawHasher(u64);
+
+impl Hasher for RawHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        bytes.iter().for_each(|byte| self.0 = (self.0 ^ *byte as u64).wrapping_mul(GOLDEN_GAMMA));
+    }
+
+    fn write_u64(&mut self, raw: u64) {
+        self.0 = split_mix(raw);
+    }
+}
+
+type Shard<O> = ParkLock<HashMap<u64, Arc<RwLock<O>>, BuildHasherDefault<RawHasher>>>;
+
+/// An unfaceted hash trie for hot read paths.<br>
+/// One level of parking_lot shards sized by core count, so a get_by or get_raw
+/// takes a single, mostly uncontended read lock and never locks the id.
+pub struct ParkTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+{
+    shards: Vec<Shard<O>>,
+    /// 64 less the bits that pick a shard
+    shift: u32,
+    sub_hsh_alg: Arc<SubHshAlg>,
+    _marker: PhantomData<I>,
+}
+
+impl<O, I> ParkTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+{
+    /// As many shards as DashMap's default, 4 per core rounded up to a power of 2
+    pub fn new() -> Self {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        Self::with_shards(cores * 4)
+    }
+
+    /// shards is rounded up to a power of 2
+    pub fn with_shards(shards: usize) -> Self {
+        let shards = shards.clamp(1, MAX_SHARDS).next_power_of_two();
+        ParkTrie {
+            shards: (0..shards).map(|_| ParkLock::new(HashMap::default())).collect(),
+            shift: 64 - shards.trailing_zeros(),
+            sub_hsh_alg: I::default().alg().clone(),
+            _marker: Default::default(),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The top bits of the rawid times GOLDEN_GAMMA, which every rawid bit reaches.<br>
+    /// Not the RawHasher split_mix: hashbrown tags each slot with its top 7 bits,
+    /// so a shard picked by them would hold keys that all share a tag.
+    fn shard(&self, raw_id: u64) -> &Shard<O> {
+        let mixed = raw_id.wrapping_mul(GOLDEN_GAMMA);
+        &self.shards[mixed.checked_shr(self.shift).unwrap_or(0) as usize]
+    }
+}
+
+impl<O, I> Default for ParkTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<O, I> IUseSubHshAlg for ParkTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+{
+    fn alg(&self) -> &Arc<SubHshAlg> {
+        &self.sub_hsh_alg
+    }
+}
+
+impl<O, I> IHashTrie<O, I> for ParkTrie<O, I>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+{
+    fn faceted(&self) -> bool {
+        false
+    }
+
+    fn size(&self) -> u64 {
+        self.shards.iter().map(|shard| shard.read().len() as u64).sum()
+    }
+
+    fn insert(&self, obj: Arc<RwLock<O>>, replace: bool) -> Option<bool> {
+        let raw_id = obj.read().unwrap().id().read().unwrap().raw_id();
+        match self.shard(raw_id).write().entry(raw_id) {
+            Entry::Occupied(mut entry) => {
+                if !replace {
+                    return Some(false);
+                }
+                entry.insert(obj);
+                None
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(obj);
+                Some(true)
+            }
+        }
+    }
+
+    fn add(&self, obj: Arc<RwLock<O>>) -> Option<bool> {
+        self.insert(obj, false)
+    }
+
+    fn get(&self, id: Arc<RwLock<I>>) -> Option<Arc<RwLock<O>>> {
+        self.get_raw(id.read().unwrap().raw_id())
+    }
+
+    fn get_raw(&self, raw_id: u64) -> Option<Arc<RwLock<O>>> {
+        self.shard(raw_id).read().get(&raw_id).cloned()
+    }
+
+    fn contains_raw(&self, raw_id: u64) -> bool {
+        self.shard(raw_id).read().contains_key(&raw_id)
+    }
+
+    fn remove(&self, id: Arc<RwLock<I>>) -> bool {
+        self.remove_raw(id.read().unwrap().raw_id())
+    }
+
+    fn remove_raw(&self, raw_id: u64) -> bool {
+        self.shard(raw_id).write().remove(&raw_id).is_some()
+    }
+
+    fn walk(&self, visit: &mut dyn FnMut(u64, &Arc<RwLock<O>>)) {
+        self.shards.iter().for_each(|shard| {
+            shard.read().iter().for_each(|(raw_id, obj)| visit(*raw_id, obj));
+        });
+    }
+
+    fn dispose(&self) {
+        self.shards.iter().for_each(|shard| shard.write().clear());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsh::{id::num_id::NumID, obj::test_obj::{obj, Obj}};
+
+    #[test]
+    fn shard_counts_round_up_to_powers_of_2() {
+        assert_eq!(ParkTrie::<Obj, NumID>::with_shards(0).shard_count(), 1);
+        assert_eq!(ParkTrie::<Obj, NumID>::with_shards(3).shard_count(), 4);
+        assert!(ParkTrie::<Obj, NumID>::new().shard_count().is_power_of_two());
+    }
+
+    #[test]
+    fn finds_what_it_holds_at_any_shard_count() {
+        [1, 4, 64].into_iter().for_each(|shards| {
+            let trie: ParkTrie<Obj, NumID> = ParkTrie::with_shards(shards);
+            (1..=1000).for_each(|raw| assert_eq!(trie.add(obj(raw)), Some(true)));
+            assert_eq!(trie.size(), 1000);
+            assert!((1..=1000).all(|raw| trie.get_by(&NumID::new(raw)).is_some()));
+            assert!((1001..=2000).all(|raw| !trie.contains_raw(raw)));
+            assert!(trie.remove_raw(500) && !trie.contains_raw(500));
+        });
+    }
+
+    /// Keys that differ only in their top bits, like the cells of one planet,
+    /// still spread over the buckets and the shards
+    #[test]
+    fn keys_differing_in_top_bits_spread() {
+        let raws: Vec<u64> = (1..=4096u64).map(|high| high << 52).collect();
+        let bucket = |raw_id: u64| {
+            let mut hasher = RawHasher::default();
+            hasher.write_u64(raw_id);
+            hasher.finish() & 1023
+        };
+        let buckets: std::collections::HashSet<u64> = raws.iter().map(|raw| bucket(*raw)).collect();
+        assert!(buckets.len() > 900, "{} of 1024 buckets", buckets.len());
+
+        let trie: ParkTrie<Obj, NumID> = ParkTrie::with_shards(16);
+        raws.iter().for_each(|raw| assert_eq!(trie.add(obj(*raw)), Some(true)));
+        assert!(raws.iter().all(|raw| trie.contains_raw(*raw)));
+        assert!(trie.shards.iter().all(|shard| shard.read().len() > 128));
+    }
+
+    /// With a shard picked by the RawHasher bits, 128 shards left one tag per shard
+    #[test]
+    fn keys_in_a_shard_spread_over_the_hashbrown_tags() {
+        let trie: ParkTrie<Obj, NumID> = ParkTrie::with_shards(1024);
+        (1..=200_000).for_each(|raw| {
+            trie.add(obj(raw));
+        });
+        let tag = |raw_id: u64| {
+            let mut hasher = RawHasher::default();
+            hasher.write_u64(raw_id);
+            hasher.finish() >> 57
+        };
+        trie.shards.iter().for_each(|shard| {
+            let shard = shard.read();
+            let mut tags = [false; 128];
+            shard.keys().for_each(|raw_id| tags[tag(*raw_id) as usize] = true);
+            assert!(shard.len() > 100);
+            assert!(tags.iter().filter(|seen| **seen).count() > 64);
+        });
+    }
+}
@@ -20,3 +20,5 @@
 pub mod str_alg;
 pub mod str_id;
 pub mod str_obj;
+pub mod str_quality;
+pub mod str_table;
@@ -19,7 +19,7 @@
  */
 #![allow(unused)]
 
-use crate::prng::fast_prng::FastPrng;
+use crate::prng::{fast_prng::{split_mix, FastPrng}, prng_trait::IPrng};
 use crate::hsh::{
     bit_cell_map::BitCellMap,
     sub_hsh_alg::ISubHshAlg,
@@ -34,9 +34,9 @@ use std::{
 };
 
 static STR_VEC: Lazy<Arc<Vec<u8>>> = Lazy::new(|| Arc::new(vec![64u8]));
-static STR_MAP: Lazy<Arc<BitCellMap>> = Lazy::new(|| Arc::new(BitCellMap::new(&STR_VEC.deref())));
+static STR_MAP: Lazy<Arc<BitCellMap>> = Lazy::new(|| Arc::new(BitCellMap::new(STR_VEC.deref())));
 pub(super) static STR_SUB_ALG: Lazy<Arc<SubHshAlg>> =
-    Lazy::new(|| Arc::new(SubHshAlg::new(4, &STR_MAP.deref())));
+    Lazy::new(|| SubHshAlg::register("str", 4, STR_MAP.deref()));
 static STR_ALG: Lazy<Arc<StrAlg>> = Lazy::new(|| Arc::new(StrAlg::singleton()));
 
 pub trait IStrAlg: ISubHshAlg {
@@ -44,7 +44,10 @@ pub trait IStrAlg: ISubHshAlg {
 }
 
 /// Collision generates up to 8 facets of a 512-bit hash.
-/// Most will only need the first 64 bits.
+/// Most will only need the first 64 bits.<br>
+/// Facet 0, the raw_id, mixes walks over both halves of the str.
+/// The others walk half each: even facets the back half, odd ones the front,
+/// see str_quality for what that costs them.<br>
 /// Testing has shown use of the first 3 facets for millions of ids. 
 pub struct StrAlg;
 
@@ -59,12 +62,13 @@ impl StrAlg {
     //noinspection t
     fn base_id(str: &str, bkd: bool, inv: bool, half: i64, bkbit: bool) -> u64 {
         const _7B: u64 = 127;
-        let len = str.len() as i64;
+        let chars: Vec<char> = str.chars().collect();
+        // count chars not bytes, chars is indexed by char
+        let len = chars.len() as i64;
         let end = half;//if bkd { 0 } else { len - 1 };
         let mut prng = FastPrng::new();
         let mut c = if bkd { len } else { -1 };
         let mut id: Wrapping<u64> = Wrapping::from_u64(0).unwrap();
-        let chars: Vec<char> = str.chars().collect();
 
         prng.push(0);
 
@@ -121,6 +125,10 @@ impl ISubHshAlg for StrAlg {
         STR_SUB_ALG.deref().id()
     }
 
+    fn name(&self) -> &str {
+        STR_SUB_ALG.deref().name()
+    }
+
     fn get_cell_map(&self) -> &Arc<BitCellMap> {
         STR_MAP.deref()
     }
@@ -131,15 +139,20 @@ impl ISubHshAlg for StrAlg {
 }
 
 impl IStrAlg for StrAlg {
+    /// Walks chars, so ascii ids are as they always were
+    /// and other strs hash where they used to panic
     fn make_id(str: &str, facet: u8) -> u64 {
-        let len = str.len();
+        let len = str.chars().count();
         if len == 0 {
             return 0;
         }
         let half: i64 = (len / 2) as i64;
 
         match facet {
-            0 => Self::base_id(str, true, false, half, false),
+            // each walk covers half the str, the raw_id mixes both
+            0 => split_mix(
+                Self::base_id(str, true, false, half, false) ^ split_mix(Self::base_id(str, false, false, half, false))
+            ),
             1 => Self::base_id(str, false, false, half, false),
             2 => Self::base_id(str, true, true, half, false),
             3 => Self::base_id(str, false, true, half, false),
@@ -151,3 +164,28 @@ impl IStrAlg for StrAlg {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_ids_are_pinned() {
+        let pinned: [u64; 8] = [
+            0x35f00de30a363f9e, 0xcbc5098d389d7c95, 0x683876b5f933a24f, 0x65bc96effca5fcef,
+            0x9d95c5bbfb99fd35, 0xdb1fa6ba1dbc8235, 0x93ebdac139a97c4f, 0x5661f9c31786f74f,
+        ];
+        (0..8).for_each(|facet| assert_eq!(StrAlg::make_id("stone", facet), pinned[facet as usize]));
+    }
+
+    #[test]
+    fn non_ascii_strs_hash() {
+        let strs = ["é", "héllo wörld", "日本語の文字列", "🦀🦀", "a🦀b"];
+        strs.iter().for_each(|str| {
+            let facets: Vec<u64> = (0..8).map(|facet| StrAlg::make_id(str, facet)).collect();
+            assert!(facets.iter().all(|facet| *facet != 0), "{str}");
+        });
+        let raw_ids: Vec<u64> = strs.iter().map(|str| StrAlg::make_id(str, 0)).collect();
+        assert!(raw_ids.iter().enumerate().all(|(at, raw)| !raw_ids[at + 1..].contains(raw)));
+    }
+}
@@ -26,54 +26,171 @@ use crate::hsh::{
         IStrAlg,
         StrAlg
     },
+    str::str_table::StrTable,
     sub_hsh_alg::{
         ISubHshAlg,
         IUseSubHshAlg,
         SubHshAlg
     }
 };
+use bevy::{
+    ecs::reflect::ReflectComponent,
+    reflect::{Reflect, ReflectDeserialize, ReflectSerialize}
+};
+use serde::{Deserialize, Serialize};
 use std::{
     any::Any,
+    hash::{Hash, Hasher},
     ops::Deref,
     sync::{Arc, RwLock}
 };
 
 pub trait IStrID: INumID {
     fn get_str(&self) -> &str;
-    fn new_str_id(str: &'static str) -> Self
+    /// The full 512-bit hash, all 8 facets big endian,
+    /// usable as a content fingerprint<br>
+    /// A one char str walks the same char both ways, so its facets repeat in pairs.
+    fn digest(&self) -> [u8; 64] {
+        let mut digest = [0u8; 64];
+        digest
+            .chunks_exact_mut(8)
+            .enumerate()
+            .for_each(|(facet, chunk)| {
+                chunk.copy_from_slice(&self.raw_facet(facet as u8).to_be_bytes())
+            });
+        digest
+    }
+    fn new_str_id(str: &str) -> Self
     where
         Self: Sized
     ;
 }
 
-/// example of a non-numeric ID
+/// example of a non-numeric ID<br>
+/// The str is interned in the StrTable, so equal StrIDs share it.
+#[derive(Serialize, Deserialize, Reflect)]
+#[serde(into = "StrIDSer", try_from = "StrIDSer")]
+#[reflect(opaque, Component, Serialize, Deserialize)]
 pub struct StrID {
-    sid: Arc<RwLock<[u64; 4]>>,
-    _str: &'static str,
+    sid: Arc<RwLock<[u64; 8]>>,
+    _str: Arc<str>,
+}
+
+/// Persisted form of a StrID, facets are rebuilt from the str on load
+#[derive(Serialize, Deserialize)]
+struct StrIDSer {
+    str: String,
+    alg: String,
+}
+
+impl From<StrID> for StrIDSer {
+    fn from(id: StrID) -> Self {
+        let alg = id.alg().name().to_string();
+        StrIDSer {
+            str: id._str.to_string(),
+            alg,
+        }
+    }
 }
 
-const SID_END: u8 = 3;
+impl TryFrom<StrIDSer> for StrID {
+    type Error = String;
+
+    fn try_from(ser: StrIDSer) -> Result<Self, Self::Error> {
+        STR_SUB_ALG.expect_name(&ser.alg)?;
+        Ok(StrID::new(&ser.str))
+    }
+}
+
+const SID_END: u8 = 7;
 
 impl StrID {
     pub fn get_string_alg() -> &'static Arc<StrAlg> {
         StrAlg::inst()
     }
     
-    pub fn new(str: &'static str) -> Self {
+    pub fn new(str: &str) -> Self {
+        let (str, raw) = StrTable::inst().intern_raw(str);
+        Self::interned(str, raw)
+    }
+
+    /// Interns without copying when str is not in the StrTable yet
+    pub fn from_arc(str: Arc<str>) -> Self {
+        let (str, raw) = StrTable::inst().intern_arc_raw(str);
+        Self::interned(str, raw)
+    }
+
+    /// Every live interned str with this raw_id
+    pub fn lookup(raw_id: u64) -> Vec<Arc<str>> {
+        StrTable::inst().lookup(raw_id)
+    }
+
+    /// The shared str of this id
+    pub fn get_arc(&self) -> &Arc<str> {
+        &self._str
+    }
+
+    /// Skips the StrTable so tests can make ids collide
+    #[cfg(test)]
+    pub(crate) fn with_raw_id(str: &str, raw: u64) -> Self {
+        Self::interned(Arc::from(str), raw)
+    }
+
+    fn interned(str: Arc<str>, raw: u64) -> Self {
         StrID {
             _str: str,
-            sid: Arc::new(RwLock::new([0, 0, 0, 0])),
+            sid: Arc::new(RwLock::new([raw, 0, 0, 0, 0, 0, 0, 0])),
         }
     }
 }
 
+impl From<&str> for StrID {
+    fn from(str: &str) -> Self {
+        StrID::new(str)
+    }
+}
+
+impl From<String> for StrID {
+    fn from(str: String) -> Self {
+        StrID::from_arc(Arc::from(str))
+    }
+}
+
+impl From<Arc<str>> for StrID {
+    fn from(str: Arc<str>) -> Self {
+        StrID::from_arc(str)
+    }
+}
+
+impl PartialEq for StrID {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_str() == other.get_str()
+    }
+}
+
+impl Eq for StrID {}
+
+impl Hash for StrID {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // the str decides equality, facets are derived from it
+        self.get_str().hash(state);
+    }
+}
+
+impl std::fmt::Debug for StrID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StrID")
+            .field("str", &self.get_str())
+            .finish()
+    }
+}
 
 impl Clone for StrID {
     fn clone(&self) -> Self {
-        let s = self._str;
-        let mut id = Self::new_str_id(s);
-        id.sid = self.sid.clone();
-        id
+        StrID {
+            _str: self._str.clone(),
+            sid: self.sid.clone(),
+        }
     }
 }
 
@@ -88,7 +205,7 @@ impl IStrID for StrID {
         &self._str
     }
 
-    fn new_str_id(str: &'static str) -> Self
+    fn new_str_id(str: &str) -> Self
     where
         Self: Sized
     {
@@ -118,11 +235,10 @@ impl INumID for StrID {
 
     fn same_id<'a>(&self, id: &Arc<RwLock<(dyn INumID + 'a)>>) -> bool {
         let read_id = id.read().unwrap();
-        if read_id.alg().id() == self.alg().id() {
-            let string_id = read_id.as_any().downcast_ref::<StrID>();
-            if string_id.is_some(){
-                return string_id.unwrap().get_str() == self._str;
-            }
+        if read_id.alg().id() == self.alg().id()
+            && let Some(string_id) = read_id.as_any().downcast_ref::<StrID>()
+        {
+            return string_id.get_str() == self.get_str();
         }
         false
     }
@@ -142,3 +258,71 @@ impl IUseSubHshAlg for StrID {
         STR_SUB_ALG.deref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::DefaultHasher;
+
+    fn hex(digest: &[u8; 64]) -> String {
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn hash_of(id: &StrID) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn round_trips_by_str() {
+        let json = serde_json::to_string(&StrID::new("stone")).unwrap();
+        assert_eq!(json, r#"{"str":"stone","alg":"str"}"#);
+        let id: StrID = serde_json::from_str(&json).unwrap();
+        assert_eq!(id, StrID::new("stone"));
+        assert_eq!(id.raw_id(), StrID::new("stone").raw_id());
+        assert!(serde_json::from_str::<StrID>(r#"{"str":"stone","alg":"num"}"#).is_err());
+    }
+
+    #[test]
+    fn digests_are_pinned() {
+        assert_eq!(
+            hex(&StrID::new("stone").digest()),
+            concat!(
+                "35f00de30a363f9ecbc5098d389d7c95683876b5f933a24f65bc96effca5fcef",
+                "9d95c5bbfb99fd35db1fa6ba1dbc823593ebdac139a97c4f5661f9c31786f74f"
+            )
+        );
+        assert_eq!(StrID::new("").digest(), [0u8; 64]);
+        assert_eq!(&StrID::new("stone").digest()[..8], &StrID::new("stone").raw_id().to_be_bytes());
+    }
+
+    #[test]
+    fn facets_are_pairwise_distinct() {
+        ["ab", "stone", "a much longer str with spaces", "日本語"].iter().for_each(|str| {
+            let id = StrID::new(str);
+            let facets: Vec<u64> = (0..=SID_END).map(|facet| id.raw_facet(facet)).collect();
+            assert!(facets.iter().enumerate().all(|(at, facet)| !facets[at + 1..].contains(facet)), "{str}");
+            assert_eq!(id.raw_facet(SID_END + 1), 0);
+        });
+        // facet 0 mixes both walks, the others pair up on a single char
+        let one = StrID::new("a");
+        (2..=SID_END).step_by(2).for_each(|facet| assert_eq!(one.raw_facet(facet), one.raw_facet(facet + 1)));
+    }
+
+    #[test]
+    fn every_source_makes_the_same_id() {
+        let from_str = StrID::from("granite");
+        let from_string = StrID::from(String::from("granite"));
+        let from_arc = StrID::from(Arc::<str>::from("granite"));
+        assert_eq!(from_str, from_string);
+        assert_eq!(from_str, from_arc);
+        assert_eq!(hash_of(&from_str), hash_of(&from_string));
+        assert_eq!(hash_of(&from_str), hash_of(&from_arc));
+        assert_eq!(from_str.raw_id(), from_arc.raw_id());
+        assert_eq!(from_str.digest(), from_string.digest());
+        // interned, so all three share one str
+        assert!(Arc::ptr_eq(from_str.get_arc(), from_arc.get_arc()));
+        assert_ne!(from_str, StrID::from("basalt"));
+    }
+}
@@ -19,6 +19,10 @@
  */
 #![allow(unused)]
 
+use serde::{
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer
+};
 use std::{
     ops::Deref,
     sync::Arc
@@ -47,7 +51,7 @@ impl<I> StrObj<I>
 where
     I: IStrID + Clone + Default,
 {
-    pub fn new(str: &'static str) -> Self {
+    pub fn new(str: &str) -> Self {
         let id = Arc::new(RwLock::new(I::new_str_id(str)));
         Self { str_id: id }
     }
@@ -71,5 +75,30 @@ where
     }
 }
 
-impl<'a, I> IStrObj<I> for StrObj<I>
-where I: IStrID + Clone + Default, {}
\ No newline at end of file
+impl<I> IStrObj<I> for StrObj<I>
+where I: IStrID + Clone + Default, {}
+
+impl<I> Serialize for StrObj<I>
+where
+    I: IStrID + Clone + Default + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut obj = serializer.serialize_struct("StrObj", 1)?;
+        obj.serialize_field("id", &self.str_id)?;
+        obj.end()
+    }
+}
+
+impl<'de, I> Deserialize<'de> for StrObj<I>
+where
+    I: IStrID + Clone + Default + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct StrObjDe<I> {
+            id: I,
+        }
+        let de = StrObjDe::<I>::deserialize(deserializer)?;
+        Ok(Self { str_id: Arc::new(RwLock::new(de.id)) })
+    }
+}
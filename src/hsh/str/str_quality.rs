@@ -0,0 +1,315 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{
+    str::str_alg::{IStrAlg, StrAlg},
+    sub_hsh_alg::ISubHshAlg
+};
+use crate::prng::{fast_prng::FastPrng, prng_trait::IPrng};
+use statrs::distribution::{ChiSquared, ContinuousCDF};
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter},
+    hash::{DefaultHasher, Hash, Hasher}
+};
+
+const MAX_LEN: u64 = 32;
+const PRINTABLE: u64 = 95;
+
+/// how far avalanche_mean may stray from 0.5, SipHash stays well inside
+const AVALANCHE_SLACK: f64 = 0.01;
+const WORST_SLACK: f64 = 0.03;
+const BIAS_MAX: f64 = 0.02;
+const BUCKET_P_MIN: f64 = 1e-4;
+
+/// A hash under test
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StrHasher {
+    /// StrAlg::make_id of one facet
+    Facet(u8),
+    /// std SipHash, the reference to compare against
+    Reference,
+}
+
+impl StrHasher {
+    pub fn hash(&self, str: &str) -> u64 {
+        match self {
+            StrHasher::Facet(facet) => StrAlg::make_id(str, *facet),
+            StrHasher::Reference => {
+                let mut hasher = DefaultHasher::new();
+                str.hash(&mut hasher);
+                hasher.finish()
+            }
+        }
+    }
+}
+
+/// The measured quality of one StrHasher.<br>
+/// avalanche and bias are ideal at 0.5 and 0.0,
+/// bucket p-values should rarely fall below 0.01.
+#[derive(Clone, Debug)]
+pub struct HashQuality {
+    pub hasher: StrHasher,
+    /// mean fraction of output bits flipped by flipping one input bit
+    pub avalanche_mean: f64,
+    /// output bit whose flip rate is furthest from 0.5
+    pub avalanche_worst: f64,
+    /// output bit whose rate of ones is furthest from 0.5, as a distance
+    pub bias_worst: f64,
+    /// chi-square p-value of sub_hsh buckets at each depth
+    pub bucket_p: Vec<f64>,
+    pub collisions: usize,
+}
+
+impl HashQuality {
+    /// What keeps this hasher from making good raw_ids, empty if nothing.<br>
+    /// The thresholds are ones SipHash meets at tens of thousands of samples.
+    pub fn weaknesses(&self) -> Vec<String> {
+        let mut weaknesses = vec![];
+        if AVALANCHE_SLACK < (self.avalanche_mean - 0.5).abs() {
+            weaknesses.push(format!("mean avalanche {:.4}", self.avalanche_mean));
+        }
+        if WORST_SLACK < (self.avalanche_worst - 0.5).abs() {
+            weaknesses.push(format!("worst avalanche {:.4}", self.avalanche_worst));
+        }
+        if BIAS_MAX < self.bias_worst {
+            weaknesses.push(format!("bit bias {:.4}", self.bias_worst));
+        }
+        self.bucket_p
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| **p < BUCKET_P_MIN)
+            .for_each(|(depth, p)| weaknesses.push(format!("depth {} bucket p {:.2e}", depth, p)));
+        if 0 < self.collisions {
+            weaknesses.push(format!("{} collisions", self.collisions));
+        }
+        weaknesses
+    }
+}
+
+impl Display for HashQuality {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let min_p = self.bucket_p.iter().cloned().fold(1.0, f64::min);
+        write!(
+            f,
+            "{:?}: avalanche {:.4} (worst {:.4}) bias {:.4} min bucket p {:.4} collisions {}",
+            self.hasher,
+            self.avalanche_mean,
+            self.avalanche_worst,
+            self.bias_worst,
+            min_p,
+            self.collisions
+        )
+    }
+}
+
+/// Statistical harness for StrAlg over generated printable ascii strs.<br>
+/// Use millions of samples for meaningful collision counts,
+/// benches/str_alg.rs times the same hashers.
+pub struct StrQuality {
+    strs: Vec<String>,
+    seed: u64,
+}
+
+impl StrQuality {
+    /// Generates distinct strs of 1 to 32 chars
+    pub fn new(samples: usize, seed: u64) -> Self {
+        let mut prng = FastPrng::new();
+        prng.seed(seed);
+        let mut seen: HashSet<String> = HashSet::with_capacity(samples);
+        while seen.len() < samples {
+            let len = 1 + prng.u64() % MAX_LEN;
+            let str: String = (0..len)
+                .map(|_| (b' ' + (prng.u64() % PRINTABLE) as u8) as char)
+                .collect();
+            seen.insert(str);
+        }
+        let mut strs: Vec<String> = seen.into_iter().collect();
+        strs.sort();
+        StrQuality { strs, seed }
+    }
+
+    pub fn strs(&self) -> &[String] {
+        &self.strs
+    }
+
+    /// Every StrAlg facet and the reference
+    pub fn compare(&self) -> Vec<HashQuality> {
+        let mut hashers: Vec<StrHasher> = (0..8).map(StrHasher::Facet).collect();
+        hashers.push(StrHasher::Reference);
+        hashers.iter().map(|hasher| self.measure(*hasher)).collect()
+    }
+
+    pub fn measure(&self, hasher: StrHasher) -> HashQuality {
+        let (avalanche_mean, avalanche_worst) = self.avalanche(hasher);
+        let hashes = self.hashes(hasher);
+        let dir_size = StrAlg::inst().dir_size() as u32;
+        let depths = (64 / dir_size.trailing_zeros()) as u8;
+        HashQuality {
+            hasher,
+            avalanche_mean,
+            avalanche_worst,
+            bias_worst: Self::bit_bias(&hashes),
+            bucket_p: (0..depths).map(|depth| Self::buckets(&hashes, depth)).collect(),
+            collisions: Self::collisions(&hashes),
+        }
+    }
+
+    pub fn hashes(&self, hasher: StrHasher) -> Vec<u64> {
+        self.strs.iter().map(|str| hasher.hash(str)).collect()
+    }
+
+    /// Flips one random bit of one random char of each str.<br>
+    /// Returns the mean flip rate and the worst output bit's flip rate.
+    pub fn avalanche(&self, hasher: StrHasher) -> (f64, f64) {
+        let mut prng = FastPrng::new();
+        prng.seed(self.seed ^ !0);
+        let mut flips = [0u64; 64];
+        self.strs.iter().for_each(|str| {
+            let mut bytes = str.clone().into_bytes();
+            let at = (prng.u64() % bytes.len() as u64) as usize;
+            // ascii stays ascii when flipping the low 7 bits
+            bytes[at] ^= 1 << (prng.u64() % 7);
+            let flipped = String::from_utf8(bytes).unwrap();
+            let diff = hasher.hash(str) ^ hasher.hash(&flipped);
+            (0..64).for_each(|bit| flips[bit] += (diff >> bit) & 1);
+        });
+        let n = self.strs.len() as f64;
+        let mean = flips.iter().sum::<u64>() as f64 / (64.0 * n);
+        let worst = flips
+            .iter()
+            .map(|flip| *flip as f64 / n)
+            .fold(0.5, |worst, rate| if (rate - 0.5).abs() > (worst - 0.5f64).abs() { rate } else { worst });
+        (mean, worst)
+    }
+
+    /// Distance from 0.5 of the most biased output bit
+    pub fn bit_bias(hashes: &[u64]) -> f64 {
+        let n = hashes.len() as f64;
+        (0..64)
+            .map(|bit| {
+                let ones = hashes.iter().filter(|hash| (*hash >> bit) & 1 == 1).count();
+                (ones as f64 / n - 0.5).abs()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    /// Chi-square p-value of how evenly sub_hsh spreads the hashes at a depth
+    pub fn buckets(hashes: &[u64], depth: u8) -> f64 {
+        let alg = StrAlg::inst();
+        let size = alg.dir_size() as usize;
+        let mut counts = vec![0u64; size];
+        hashes.iter().for_each(|hash| counts[alg.sub_hsh(*hash, depth) as usize] += 1);
+        let expected = hashes.len() as f64 / size as f64;
+        let chi_sq: f64 = counts
+            .iter()
+            .map(|count| {
+                let d = *count as f64 - expected;
+                d * d / expected
+            })
+            .sum();
+        ChiSquared::new((size - 1) as f64).unwrap().sf(chi_sq)
+    }
+
+    /// Distinct strs that share a hash with an earlier str
+    pub fn collisions(hashes: &[u64]) -> usize {
+        let mut sorted = hashes.to_vec();
+        sorted.sort_unstable();
+        sorted.windows(2).filter(|pair| pair[0] == pair[1]).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 20_000;
+    const SEEDS: [u64; 3] = [1, 2, 3];
+
+    #[test]
+    fn reference_has_no_weaknesses() {
+        SEEDS.iter().for_each(|seed| {
+            let quality = StrQuality::new(SAMPLES, *seed).measure(StrHasher::Reference);
+            assert!(quality.weaknesses().is_empty(), "{quality}: {:?}", quality.weaknesses());
+        });
+    }
+
+    #[test]
+    fn facet_0_has_no_weaknesses() {
+        SEEDS.iter().for_each(|seed| {
+            let quality = StrQuality::new(SAMPLES, *seed).measure(StrHasher::Facet(0));
+            assert!(quality.weaknesses().is_empty(), "{quality}: {:?}", quality.weaknesses());
+        });
+    }
+
+    /// facet 1 walks only the front half of the str,
+    /// so the harness has to report it, at this size as low avalanche
+    #[test]
+    fn reports_the_half_str_weakness_of_facet_1() {
+        SEEDS.iter().for_each(|seed| {
+            let quality = StrQuality::new(SAMPLES, *seed).measure(StrHasher::Facet(1));
+            let weaknesses = quality.weaknesses();
+            assert!(weaknesses.iter().any(|weakness| weakness.starts_with("mean avalanche")), "{quality}");
+            assert!(quality.avalanche_mean < 0.5 - AVALANCHE_SLACK, "{quality}");
+        });
+    }
+
+    /// facet 0 used to walk only the back half
+    #[test]
+    fn facet_0_sees_both_halves() {
+        assert_ne!(StrAlg::make_id("abcdefgh", 0), StrAlg::make_id("XYZdefgh", 0));
+        assert_ne!(StrAlg::make_id("abcdefgh", 0), StrAlg::make_id("abcdXYZh", 0));
+    }
+
+    /// Collision counts need millions of strs, about a minute optimized.<br>
+    /// Facets 1 and on walk half the str, so each collides on its own,
+    /// facet 0 walks both halves and tells every str apart.
+    #[test]
+    #[ignore = "millions of strs, run with --ignored"]
+    fn collisions_over_millions_of_strs() {
+        let quality = StrQuality::new(2_000_000, 1);
+        let reference = quality.measure(StrHasher::Reference);
+        assert!(reference.weaknesses().is_empty(), "{reference}: {:?}", reference.weaknesses());
+        let facet_0 = quality.measure(StrHasher::Facet(0));
+        assert_eq!(facet_0.collisions, 0, "{facet_0}");
+        (1..8).for_each(|facet| {
+            let measured = quality.measure(StrHasher::Facet(facet));
+            assert!(measured.weaknesses().iter().any(|weakness| weakness.ends_with("collisions")), "{measured}");
+        });
+    }
+
+    #[test]
+    fn compare_measures_every_facet() {
+        let qualities = StrQuality::new(2000, 1).compare();
+        assert_eq!(qualities.len(), 9);
+        assert_eq!(qualities[8].hasher, StrHasher::Reference);
+    }
+
+    #[test]
+    fn generated_strs_are_distinct_printable() {
+        let quality = StrQuality::new(1000, 1);
+        let distinct: HashSet<&String> = quality.strs().iter().collect();
+        assert_eq!(distinct.len(), 1000);
+        assert!(quality.strs().iter().all(|str| {
+            (1..=MAX_LEN as usize).contains(&str.len()) && str.bytes().all(|byte| byte.is_ascii_graphic() || byte == b' ')
+        }));
+    }
+}
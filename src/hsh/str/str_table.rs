@@ -0,0 +1,173 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::str::str_alg::{IStrAlg, StrAlg};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::{
+    ops::Deref,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak
+    }
+};
+
+static STR_TABLE: Lazy<StrTable> = Lazy::new(StrTable::new);
+
+/// inserts between full sweeps, at the least
+const SWEEP_MIN: usize = 1024;
+
+/// Interns the strs of StrIDs so equal live strs share one allocation,
+/// and finds strs again by their raw_id.<br>
+/// The table only holds Weak refs, a str is freed once its last StrID drops.
+/// Dead entries are pruned when their raw_id is interned again,
+/// and every entry is swept once inserts pass twice the table size.
+pub struct StrTable {
+    raws: DashMap<u64, Vec<Weak<str>>>,
+    inserts: AtomicUsize,
+    sweep_at: AtomicUsize,
+}
+
+impl StrTable {
+    fn new() -> Self {
+        StrTable {
+            raws: DashMap::new(),
+            inserts: AtomicUsize::new(0),
+            sweep_at: AtomicUsize::new(SWEEP_MIN),
+        }
+    }
+
+    pub fn inst() -> &'static StrTable {
+        STR_TABLE.deref()
+    }
+
+    pub fn intern(&self, str: &str) -> Arc<str> {
+        self.intern_raw(str).0
+    }
+
+    /// Interns without copying when str is not there yet
+    pub fn intern_arc(&self, str: Arc<str>) -> Arc<str> {
+        self.intern_arc_raw(str).0
+    }
+
+    pub fn intern_arc_raw(&self, str: Arc<str>) -> (Arc<str>, u64) {
+        self.insert(&str.clone(), || str)
+    }
+
+    /// Returns the shared str and its raw_id (facet 0)
+    pub fn intern_raw(&self, str: &str) -> (Arc<str>, u64) {
+        self.insert(str, || Arc::from(str))
+    }
+
+    /// Finds a live equal str or adds the one make builds,
+    /// the raw_id's entry is locked throughout
+    fn insert(&self, str: &str, make: impl FnOnce() -> Arc<str>) -> (Arc<str>, u64) {
+        let raw = StrAlg::make_id(str, 0);
+        let shared = {
+            let mut weaks = self.raws.entry(raw).or_default();
+            weaks.retain(|weak| 0 < weak.strong_count());
+            if let Some(live) = weaks.iter().find_map(|weak| weak.upgrade().filter(|live| live.deref() == str)) {
+                return (live, raw);
+            }
+            let shared = make();
+            weaks.push(Arc::downgrade(&shared));
+            shared
+        };
+        if self.sweep_at.load(Ordering::Relaxed) <= self.inserts.fetch_add(1, Ordering::Relaxed) + 1 {
+            self.prune();
+        }
+        (shared, raw)
+    }
+
+    /// Every live interned str with this raw_id, more than one on a collision
+    pub fn lookup(&self, raw_id: u64) -> Vec<Arc<str>> {
+        match self.raws.get(&raw_id) {
+            Some(weaks) => weaks.iter().filter_map(Weak::upgrade).collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn contains(&self, str: &str) -> bool {
+        self.lookup(StrAlg::make_id(str, 0))
+            .iter()
+            .any(|live| live.deref() == str)
+    }
+
+    /// Live strs, dead entries not pruned yet are skipped
+    pub fn len(&self) -> usize {
+        self.raws
+            .iter()
+            .map(|weaks| weaks.iter().filter(|weak| 0 < weak.strong_count()).count())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops the entries of strs no StrID holds any more
+    pub fn prune(&self) {
+        self.raws.retain(|_, weaks| {
+            weaks.retain(|weak| 0 < weak.strong_count());
+            !weaks.is_empty()
+        });
+        self.inserts.store(0, Ordering::Relaxed);
+        self.sweep_at.store(SWEEP_MIN.max(2 * self.raws.len()), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_strs_share_while_live() {
+        let table = StrTable::new();
+        let (first, raw) = table.intern_raw("shared");
+        let second = table.intern("shared");
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(table.lookup(raw), vec![first.clone()]);
+        assert!(table.contains("shared"));
+    }
+
+    #[test]
+    fn dropped_strs_are_freed() {
+        let table = StrTable::new();
+        let (str, raw) = table.intern_raw("transient");
+        let weak = Arc::downgrade(&str);
+        drop(str);
+        assert!(weak.upgrade().is_none());
+        assert!(table.lookup(raw).is_empty());
+        assert!(!table.contains("transient"));
+        table.prune();
+        assert!(table.raws.is_empty());
+    }
+
+    #[test]
+    fn sweeps_dead_entries_as_it_grows() {
+        let table = StrTable::new();
+        (0..4 * SWEEP_MIN).for_each(|index| {
+            table.intern(&format!("player input {index}"));
+        });
+        assert!(table.raws.len() < SWEEP_MIN);
+        assert!(table.is_empty());
+    }
+}
@@ -20,8 +20,11 @@
 #![allow(unused)]
 
 use crate::hsh::bit_cell_map::BitCellMap;
-use std::sync::atomic::{AtomicI64, Ordering};
-use std::sync::Arc;
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, RwLock};
 
 /// Traits for N dimensional hash IDs
 pub trait IUseSubHshAlg {
@@ -32,7 +35,51 @@ pub trait IUseSubHshAlg {
     fn alg(&self) -> &Arc<SubHshAlg>;
 }
 
-const MAX_BIT: u8 = 10;
+/// dir_size is a u8, so 1 << 7 is the largest directory.<br>
+/// The old bound of 10 let 8 to 10 bits through to a 1 << bits that overflows a u8.
+pub(crate) const MAX_BIT: u8 = 7;
+
+/// Why a SubHshAlg could not be registered
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubHshAlgErr {
+    /// 0 sub hash bits, a one slot directory never parts rawids
+    NoBits,
+    /// sub hash bits over MAX_BIT
+    TooManyBits(u8),
+    /// the name is registered with other bits or another cell map
+    NameTaken(String),
+    /// the id of the name equals the id of another registered name
+    IdCollision { name: String, other: String },
+}
+
+impl Display for SubHshAlgErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubHshAlgErr::NoBits => write!(f, "SubHshAlg cannot be configured with 0 bits!"),
+            SubHshAlgErr::TooManyBits(bits) => write!(
+                f,
+                "SubHshAlg cannot be configured with more than {} bits, not {}!",
+                MAX_BIT, bits
+            ),
+            SubHshAlgErr::NameTaken(name) => write!(
+                f,
+                "SubHshAlg \"{}\" is already registered with another configuration!",
+                name
+            ),
+            SubHshAlgErr::IdCollision { name, other } => write!(
+                f,
+                "SubHshAlg \"{}\" has the same id as \"{}\"!",
+                name, other
+            ),
+        }
+    }
+}
+
+impl Error for SubHshAlgErr {}
+
+/// Every registered algorithm by name, a BTreeMap so listing is ordered
+static ALG_REGISTRY: Lazy<RwLock<BTreeMap<String, Arc<SubHshAlg>>>> =
+    Lazy::new(|| RwLock::new(BTreeMap::new()));
 
 /// Traits for a hash-based "type" system with Hash Tree indexing
 pub trait ISubHshAlg {
@@ -41,6 +88,8 @@ pub trait ISubHshAlg {
     /// last index of a subdirectory
     fn dir_last(&self) -> u8;
     fn id(&self) -> i64;
+    /// stable name used to persist ids that belong to this algorithm
+    fn name(&self) -> &str;
     fn get_cell_map(&self) -> &Arc<BitCellMap>;
     fn sub_hsh(&self, raw_id: u64, depth: u8) -> u8;
 }
@@ -55,21 +104,94 @@ pub struct SubHshAlg {
     size: u8,
     last: u8,
     id: i64,
+    name: String,
     cell_map: Arc<BitCellMap>,
 }
 
 impl SubHshAlg {
-    pub fn new(sub_hsh_bits: u8, map: &Arc<BitCellMap>) -> Self {
-        if sub_hsh_bits > MAX_BIT {
-            panic!(
-                "SubHshAlg cannot be configured with more than {} bits!",
-                MAX_BIT
-            );
+    /// Registers an algorithm under a name, or returns the one already there.<br>
+    /// The id is derived from the name, so it is the same in every process.<br>
+    /// Panics on any SubHshAlgErr, see try_register
+    pub fn register(name: &str, sub_hsh_bits: u8, map: &Arc<BitCellMap>) -> Arc<SubHshAlg> {
+        Self::try_register(name, sub_hsh_bits, map).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// For algorithms that come from data rather than code
+    pub fn try_register(
+        name: &str,
+        sub_hsh_bits: u8,
+        map: &Arc<BitCellMap>,
+    ) -> Result<Arc<SubHshAlg>, SubHshAlgErr> {
+        Self::register_as(name, Self::id_of(name), sub_hsh_bits, map)
+    }
+
+    /// try_register with the id given, so tests can force a collision
+    fn register_as(
+        name: &str,
+        id: i64,
+        sub_hsh_bits: u8,
+        map: &Arc<BitCellMap>,
+    ) -> Result<Arc<SubHshAlg>, SubHshAlgErr> {
+        Self::check_bits(sub_hsh_bits)?;
+        let mut registry = ALG_REGISTRY.write().unwrap();
+        if let Some(alg) = registry.get(name) {
+            if alg.bits != sub_hsh_bits || alg.cell_map.widths() != map.widths() {
+                return Err(SubHshAlgErr::NameTaken(name.to_string()));
+            }
+            return Ok(alg.clone());
+        }
+        if let Some(other) = registry.values().find(|other| other.id == id) {
+            return Err(SubHshAlgErr::IdCollision {
+                name: name.to_string(),
+                other: other.name.clone(),
+            });
         }
+        let alg = Arc::new(Self::new(name, id, sub_hsh_bits, map));
+        registry.insert(alg.name.clone(), alg.clone());
+        Ok(alg)
+    }
+
+    /// Validates sub hash bits without registering anything
+    pub fn check_bits(sub_hsh_bits: u8) -> Result<(), SubHshAlgErr> {
+        if sub_hsh_bits == 0 {
+            Err(SubHshAlgErr::NoBits)
+        } else if sub_hsh_bits > MAX_BIT {
+            Err(SubHshAlgErr::TooManyBits(sub_hsh_bits))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Finds a registered algorithm.<br>
+    /// Algorithms register when first used, see hsh::register_algs
+    pub fn by_name(name: &str) -> Option<Arc<SubHshAlg>> {
+        ALG_REGISTRY.read().unwrap().get(name).cloned()
+    }
+
+    /// All registered algorithms ordered by name
+    pub fn registered() -> Vec<Arc<SubHshAlg>> {
+        ALG_REGISTRY.read().unwrap().values().cloned().collect()
+    }
+
+    /// The id an algorithm registered under this name will have
+    pub const fn id_of(name: &str) -> i64 {
+        // FNV-1a 64
+        let bytes = name.as_bytes();
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+            i += 1;
+        }
+        hash as i64
+    }
+
+    fn new(name: &str, id: i64, sub_hsh_bits: u8, map: &Arc<BitCellMap>) -> Self {
         let bits = sub_hsh_bits;
         let size = 1 << bits;
         let last = size - 1;
-        let id = Self::id_tally();
+        let name = name.to_string();
         let cell_map = map.clone();
 
         Self {
@@ -77,20 +199,26 @@ impl SubHshAlg {
             size,
             last,
             id,
+            name,
             cell_map,
         }
     }
 
-    fn id_tally() -> i64 {
-        static ID_TALLY: AtomicI64 = AtomicI64::new(0);
-        // remember this is a post increment that returns current value before incrementing like ID_TALLY++ in c++
-        // so... to use this the way we did in C# we do a +1
-        ID_TALLY.fetch_add(1, Ordering::SeqCst) + 1
-    }
-
     pub fn same_alg(&self, alg: &Arc<SubHshAlg>) -> bool{
         alg.id == self.id
     }
+
+    /// Checks a persisted algorithm name against this algorithm
+    pub fn expect_name(&self, name: &str) -> Result<(), String> {
+        if self.name == name {
+            Ok(())
+        } else {
+            Err(format!(
+                "Expected an id of SubHshAlg \"{}\" but found \"{}\"!",
+                self.name, name
+            ))
+        }
+    }
 }
 
 impl ISubHshAlg for SubHshAlg {
@@ -103,7 +231,7 @@ impl ISubHshAlg for SubHshAlg {
     }
 
     /// <summary>
-    /// Every algorithm will have its own id.<para/>
+    /// Every algorithm will have its own id, derived from its name.<para/>
     /// Grouped algorithms can be contained in a master algorithm.<para/>
     /// Only group algorithms that are guaranteed to never collide;<para/>
     /// e.g. We need 128 bits for info, one alg covers the first 64 bits<para/>
@@ -111,13 +239,18 @@ impl ISubHshAlg for SubHshAlg {
     /// <see cref="str.StringAlg"/>str::StrAlg
     /// implements facets to group 256 bits.<para/>
     /// <see cref="source.Source{O, OI}"/>
-    /// Consider implementing Isomech PolyID to group arbitrary bits
-    /// like in Isomech source.Source and the powerful VennID.
+    /// id::poly_id::PolyID groups arbitrary bits
+    /// like in Isomech source.Source.<para/>
+    /// There is no VennID, see PolyID for why.
     /// </summary>
     fn id(&self) -> i64 {
         self.id
     }
 
+    fn name(&self) -> &str {
+        &self.name
+    }
+
     fn get_cell_map(&self) -> &Arc<BitCellMap> {
         &self.cell_map
     }
@@ -126,3 +259,85 @@ impl ISubHshAlg for SubHshAlg {
         self.last & ((raw_id >> (self.bits * depth)) as u8)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsh::register_algs;
+
+    fn map(widths: Vec<u8>) -> Arc<BitCellMap> {
+        Arc::new(BitCellMap::new(&Arc::new(widths)))
+    }
+
+    #[test]
+    fn ids_are_pinned() {
+        // persisted ids depend on these, they must never change
+        assert_eq!(SubHshAlg::id_of("num"), 2378669269878832019);
+        assert_eq!(SubHshAlg::id_of("str"), -9048150456382168848);
+        assert_eq!(SubHshAlg::id_of("poly"), -8351674761645742617);
+        register_algs();
+        assert_eq!(SubHshAlg::by_name("num").unwrap().id(), SubHshAlg::id_of("num"));
+    }
+
+    #[test]
+    fn re_registering_is_idempotent() {
+        let first = SubHshAlg::register("reg_same", 3, &map(vec![64]));
+        let again = SubHshAlg::register("reg_same", 3, &map(vec![64]));
+        assert!(Arc::ptr_eq(&first, &again));
+        assert!(Arc::ptr_eq(&SubHshAlg::by_name("reg_same").unwrap(), &first));
+    }
+
+    #[test]
+    fn a_name_keeps_its_configuration() {
+        SubHshAlg::register("reg_taken", 3, &map(vec![64]));
+        assert_eq!(
+            SubHshAlg::try_register("reg_taken", 4, &map(vec![64])).err(),
+            Some(SubHshAlgErr::NameTaken("reg_taken".to_string()))
+        );
+        assert_eq!(
+            SubHshAlg::try_register("reg_taken", 3, &map(vec![32, 32])).err(),
+            Some(SubHshAlgErr::NameTaken("reg_taken".to_string()))
+        );
+    }
+
+    #[test]
+    fn colliding_ids_are_refused() {
+        let held = SubHshAlg::register("reg_held", 3, &map(vec![64]));
+        assert_eq!(
+            SubHshAlg::register_as("reg_collides", held.id(), 3, &map(vec![64])).err(),
+            Some(SubHshAlgErr::IdCollision { name: "reg_collides".to_string(), other: "reg_held".to_string() })
+        );
+        assert!(SubHshAlg::by_name("reg_collides").is_none());
+    }
+
+    #[test]
+    fn max_bit_is_the_widest_u8_directory() {
+        let widest = SubHshAlg::register("reg_widest", MAX_BIT, &map(vec![64]));
+        assert_eq!(widest.dir_size(), 128);
+        assert_eq!(widest.dir_last(), 127);
+        assert_eq!(widest.sub_hsh(u64::MAX, 0), 127);
+        (MAX_BIT + 1..=10).for_each(|bits| {
+            assert!(1u8.checked_shl(bits as u32).is_none());
+            assert_eq!(SubHshAlg::check_bits(bits), Err(SubHshAlgErr::TooManyBits(bits)));
+        });
+    }
+
+    #[test]
+    fn zero_bits_are_refused() {
+        assert_eq!(SubHshAlg::check_bits(0), Err(SubHshAlgErr::NoBits));
+        assert_eq!(
+            SubHshAlg::try_register("reg_no_bits", 0, &map(vec![64])).err(),
+            Some(SubHshAlgErr::NoBits)
+        );
+        assert!(SubHshAlg::by_name("reg_no_bits").is_none());
+        assert!(SubHshAlg::check_bits(1).is_ok());
+    }
+
+    #[test]
+    fn registered_is_ordered_by_name() {
+        register_algs();
+        let names: Vec<String> = SubHshAlg::registered().iter().map(|alg| alg.name().to_string()).collect();
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(["num", "poly", "str"].iter().all(|name| names.iter().any(|held| held == name)));
+    }
+}
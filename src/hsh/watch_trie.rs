@@ -0,0 +1,522 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::hsh::{
+    hash_trie::IHashTrie,
+    id::num_id::INumID,
+    obj::sub_hsh_obj::ISubHshObj,
+    sub_hsh_alg::{IUseSubHshAlg, SubHshAlg}
+};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError, RwLock
+    },
+    thread::{self, ThreadId}
+};
+
+/// A change made to a WatchTrie
+pub enum TrieEvent<O> {
+    Added { raw_id: u64, obj: Arc<RwLock<O>> },
+    Replaced { raw_id: u64, old: Arc<RwLock<O>>, obj: Arc<RwLock<O>> },
+    Removed { raw_id: u64, obj: Arc<RwLock<O>> },
+    Disposed,
+}
+
+impl<O> TrieEvent<O> {
+    /// None for Disposed, which touches every rawid
+    pub fn raw_id(&self) -> Option<u64> {
+        match self {
+            TrieEvent::Added { raw_id, .. }
+            | TrieEvent::Replaced { raw_id, .. }
+            | TrieEvent::Removed { raw_id, .. } => Some(*raw_id),
+            TrieEvent::Disposed => None,
+        }
+    }
+}
+
+impl<O> Clone for TrieEvent<O> {
+    fn clone(&self) -> Self {
+        match self {
+            TrieEvent::Added { raw_id, obj } => TrieEvent::Added {
+                raw_id: *raw_id,
+                obj: obj.clone(),
+            },
+            TrieEvent::Replaced { raw_id, old, obj } => TrieEvent::Replaced {
+                raw_id: *raw_id,
+                old: old.clone(),
+                obj: obj.clone(),
+            },
+            TrieEvent::Removed { raw_id, obj } => TrieEvent::Removed {
+                raw_id: *raw_id,
+                obj: obj.clone(),
+            },
+            TrieEvent::Disposed => TrieEvent::Disposed,
+        }
+    }
+}
+
+/// The rawids that differ between two log positions
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrieDiff {
+    pub added: Vec<u64>,
+    pub replaced: Vec<u64>,
+    pub removed: Vec<u64>,
+}
+
+impl TrieDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.replaced.is_empty() && self.removed.is_empty()
+    }
+}
+
+pub type TrieSubscriber<O> = Arc<dyn Fn(&TrieEvent<O>) + Send + Sync>;
+
+/// Wraps any IHashTrie and reports every change to its subscribers,
+/// optionally keeping an append-only log of them.<br>
+/// Changes are applied one at a time, so subscribers and the log
+/// see them in the order the trie did.<br>
+/// A subscriber may read this trie and sees the change applied,
+/// but must not change it, it is called mid change and would panic.<br>
+/// A panicking subscriber leaves the change applied and logged,
+/// and the trie usable.
+pub struct WatchTrie<O, I, T>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+    T: IHashTrie<O, I>,
+{
+    trie: T,
+    subscribers: RwLock<Vec<(u64, TrieSubscriber<O>)>>,
+    next_sub: AtomicU64,
+    log: Option<RwLock<Vec<TrieEvent<O>>>>,
+    change: Mutex<()>,
+    /// The thread calling the subscribers, if any
+    emitter: RwLock<Option<ThreadId>>,
+    _marker: PhantomData<I>,
+}
+
+impl<O, I, T> WatchTrie<O, I, T>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+    T: IHashTrie<O, I>,
+{
+    pub fn new(trie: T) -> Self {
+        WatchTrie {
+            trie,
+            subscribers: RwLock::new(vec![]),
+            next_sub: AtomicU64::new(1),
+            log: None,
+            change: Mutex::new(()),
+            emitter: RwLock::new(None),
+            _marker: Default::default(),
+        }
+    }
+
+    /// Also keeps every change in a log for replay and diff.<br>
+    /// The log starts with an Added for each object trie already holds.
+    pub fn with_log(trie: T) -> Self {
+        let held = Self::held(&trie);
+        WatchTrie {
+            log: Some(RwLock::new(held)),
+            ..Self::new(trie)
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.trie
+    }
+
+    /// Returns the id to unsubscribe with
+    pub fn subscribe(&self, subscriber: impl Fn(&TrieEvent<O>) + Send + Sync + 'static) -> u64 {
+        let sub_id = self.next_sub.fetch_add(1, Ordering::Relaxed);
+        self.subscribers.write().unwrap().push((sub_id, Arc::new(subscriber)));
+        sub_id
+    }
+
+    /// returns false if not subscribed
+    pub fn unsubscribe(&self, sub_id: u64) -> bool {
+        let mut subscribers = self.subscribers.write().unwrap();
+        let len = subscribers.len();
+        subscribers.retain(|(id, _)| *id != sub_id);
+        subscribers.len() != len
+    }
+
+    pub fn logging(&self) -> bool {
+        self.log.is_some()
+    }
+
+    /// The log position after the latest change, 0 without a log
+    pub fn log_len(&self) -> usize {
+        self.log.as_ref().map_or(0, |log| log.read().unwrap().len())
+    }
+
+    /// The logged changes from position from up to to
+    pub fn events(&self, from: usize, to: usize) -> Vec<TrieEvent<O>> {
+        match &self.log {
+            Some(log) => {
+                let log = log.read().unwrap();
+                let to = to.min(log.len());
+                log[from.min(to)..to].to_vec()
+            }
+            None => vec![],
+        }
+    }
+
+    /// Applies the logged changes up to position to onto trie<br>
+    /// Replaying into an empty trie rebuilds this trie as it was at to.
+    pub fn replay<R: IHashTrie<O, I> + ?Sized>(&self, to: usize, trie: &R) {
+        self.events(0, to).into_iter().for_each(|event| match event {
+            TrieEvent::Added { obj, .. } | TrieEvent::Replaced { obj, .. } => {
+                trie.insert(obj, true);
+            }
+            TrieEvent::Removed { raw_id, .. } => {
+                trie.remove_raw(raw_id);
+            }
+            TrieEvent::Disposed => trie.dispose(),
+        });
+    }
+
+    /// What changed between log positions from and to,
+    /// an object counts as replaced only if it is a different Arc
+    pub fn diff(&self, from: usize, to: usize) -> TrieDiff {
+        let before = self.state_at(from);
+        let after = self.state_at(to);
+        let mut diff = TrieDiff::default();
+        after.iter().for_each(|(raw_id, obj)| match before.get(raw_id) {
+            None => diff.added.push(*raw_id),
+            Some(old) if !Arc::ptr_eq(old, obj) => diff.replaced.push(*raw_id),
+            _ => {}
+        });
+        before.keys()
+            .filter(|raw_id| !after.contains_key(raw_id))
+            .for_each(|raw_id| diff.removed.push(*raw_id));
+        diff.added.sort_unstable();
+        diff.replaced.sort_unstable();
+        diff.removed.sort_unstable();
+        diff
+    }
+
+    fn state_at(&self, to: usize) -> HashMap<u64, Arc<RwLock<O>>> {
+        let mut state = HashMap::new();
+        self.events(0, to).into_iter().for_each(|event| match event {
+            TrieEvent::Added { raw_id, obj } | TrieEvent::Replaced { raw_id, obj, .. } => {
+                state.insert(raw_id, obj);
+            }
+            TrieEvent::Removed { raw_id, .. } => {
+                state.remove(&raw_id);
+            }
+            TrieEvent::Disposed => state.clear(),
+        });
+        state
+    }
+
+    /// Drops the log but keeps logging,
+    /// it starts again with an Added for each object held now
+    pub fn clear_log(&self) {
+        if let Some(log) = &self.log {
+            let _change = self.lock_change();
+            *log.write().unwrap() = Self::held(&self.trie);
+        }
+    }
+
+    /// An Added for each object in trie, so replay starts from what it holds
+    fn held(trie: &T) -> Vec<TrieEvent<O>> {
+        let mut events = vec![];
+        trie.walk(&mut |raw_id, obj| events.push(TrieEvent::Added { raw_id, obj: obj.clone() }));
+        events
+    }
+
+    /// Panics on the thread calling the subscribers rather than deadlock
+    fn lock_change(&self) -> MutexGuard<'_, ()> {
+        assert_ne!(
+            *self.emitter.read().unwrap(),
+            Some(thread::current().id()),
+            "a WatchTrie subscriber changed the trie it watches"
+        );
+        // only a panicking subscriber poisons it, the trie itself is whole
+        self.change.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Logs first, so a panicking subscriber cannot leave the log behind the trie
+    fn emit(&self, event: TrieEvent<O>) {
+        if let Some(log) = &self.log {
+            log.write().unwrap().push(event.clone());
+        }
+        let subscribers: Vec<TrieSubscriber<O>> = self.subscribers
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(_, subscriber)| subscriber.clone())
+            .collect();
+        let _emitting = Emitting::new(&self.emitter);
+        subscribers.iter().for_each(|subscriber| subscriber(&event));
+    }
+}
+
+/// Marks the current thread as calling the subscribers until dropped,
+/// also when a subscriber panics
+struct Emitting<'a>(&'a RwLock<Option<ThreadId>>);
+
+impl<'a> Emitting<'a> {
+    fn new(emitter: &'a RwLock<Option<ThreadId>>) -> Self {
+        *emitter.write().unwrap() = Some(thread::current().id());
+        Emitting(emitter)
+    }
+}
+
+impl Drop for Emitting<'_> {
+    fn drop(&mut self) {
+        *self.0.write().unwrap() = None;
+    }
+}
+
+impl<O, I, T> IUseSubHshAlg for WatchTrie<O, I, T>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+    T: IHashTrie<O, I>,
+{
+    fn alg(&self) -> &Arc<SubHshAlg> {
+        self.trie.alg()
+    }
+}
+
+impl<O, I, T> IHashTrie<O, I> for WatchTrie<O, I, T>
+where
+    O: ISubHshObj<I>,
+    I: INumID + Default,
+    T: IHashTrie<O, I>,
+{
+    fn faceted(&self) -> bool {
+        self.trie.faceted()
+    }
+
+    fn size(&self) -> u64 {
+        self.trie.size()
+    }
+
+    fn insert(&self, obj: Arc<RwLock<O>>, replace: bool) -> Option<bool> {
+        let raw_id = obj.read().unwrap().id().read().unwrap().raw_id();
+        let _change = self.lock_change();
+        let old = self.trie.get_raw(raw_id);
+        let stored = self.trie.insert(obj.clone(), replace);
+        match (stored, old) {
+            (Some(true), _) => self.emit(TrieEvent::Added { raw_id, obj }),
+            (None, Some(old)) => self.emit(TrieEvent::Replaced { raw_id, old, obj }),
+            _ => {}
+        }
+        stored
+    }
+
+    fn add(&self, obj: Arc<RwLock<O>>) -> Option<bool> {
+        self.insert(obj, false)
+    }
+
+    fn get(&self, id: Arc<RwLock<I>>) -> Option<Arc<RwLock<O>>> {
+        self.trie.get(id)
+    }
+
+    fn get_raw(&self, raw_id: u64) -> Option<Arc<RwLock<O>>> {
+        self.trie.get_raw(raw_id)
+    }
+
+    fn contains_raw(&self, raw_id: u64) -> bool {
+        self.trie.contains_raw(raw_id)
+    }
+
+    fn remove(&self, id: Arc<RwLock<I>>) -> bool {
+        self.remove_raw(id.read().unwrap().raw_id())
+    }
+
+    fn remove_raw(&self, raw_id: u64) -> bool {
+        let _change = self.lock_change();
+        let Some(obj) = self.trie.get_raw(raw_id) else { return false; };
+        let removed = self.trie.remove_raw(raw_id);
+        if removed {
+            self.emit(TrieEvent::Removed { raw_id, obj });
+        }
+        removed
+    }
+
+    fn walk(&self, visit: &mut dyn FnMut(u64, &Arc<RwLock<O>>)) {
+        self.trie.walk(visit)
+    }
+
+    fn dispose(&self) {
+        let _change = self.lock_change();
+        self.trie.dispose();
+        self.emit(TrieEvent::Disposed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsh::{dash_trie::DashTrie, id::num_id::NumID, obj::test_obj::{obj, Obj}};
+
+    fn raw_ids<R: IHashTrie<Obj, NumID>>(trie: &R) -> Vec<u64> {
+        let mut raw_ids = vec![];
+        trie.walk(&mut |raw_id, _| raw_ids.push(raw_id));
+        raw_ids.sort_unstable();
+        raw_ids
+    }
+
+    #[test]
+    fn replay_includes_what_the_trie_held() {
+        let trie: DashTrie<Obj, NumID> = DashTrie::new();
+        (1..=3).for_each(|raw| { trie.add(obj(raw)); });
+        let watch = WatchTrie::with_log(trie);
+        assert_eq!(watch.log_len(), 3);
+        watch.add(obj(4));
+        watch.remove_raw(2);
+
+        let rebuilt: DashTrie<Obj, NumID> = DashTrie::new();
+        watch.replay(watch.log_len(), &rebuilt);
+        assert_eq!(raw_ids(&rebuilt), raw_ids(&watch));
+        let diff = watch.diff(3, watch.log_len());
+        assert_eq!((diff.added, diff.removed), (vec![4], vec![2]));
+    }
+
+    #[test]
+    fn cleared_log_restarts_from_what_is_held() {
+        let watch = WatchTrie::with_log(DashTrie::<Obj, NumID>::new());
+        (1..=5).for_each(|raw| { watch.add(obj(raw)); });
+        watch.remove_raw(1);
+        watch.clear_log();
+        assert_eq!(watch.log_len(), 4);
+        watch.insert(obj(3), true);
+
+        let rebuilt: DashTrie<Obj, NumID> = DashTrie::new();
+        watch.replay(watch.log_len(), &rebuilt);
+        assert_eq!(raw_ids(&rebuilt), vec![2, 3, 4, 5]);
+        assert_eq!(watch.diff(4, watch.log_len()).replaced, vec![3]);
+    }
+
+    /// (kind, raw_id, obj, old) per event: A added, R replaced, D removed, X disposed
+    type Seen = Arc<Mutex<Vec<(char, Option<u64>, Option<Arc<RwLock<Obj>>>, Option<Arc<RwLock<Obj>>>)>>>;
+
+    fn record(seen: &Seen) -> impl Fn(&TrieEvent<Obj>) + Send + Sync + 'static {
+        let seen = seen.clone();
+        move |event| {
+            let entry = match event {
+                TrieEvent::Added { raw_id, obj } => ('A', Some(*raw_id), Some(obj.clone()), None),
+                TrieEvent::Replaced { raw_id, old, obj } => ('R', Some(*raw_id), Some(obj.clone()), Some(old.clone())),
+                TrieEvent::Removed { raw_id, obj } => ('D', Some(*raw_id), Some(obj.clone()), None),
+                TrieEvent::Disposed => ('X', None, None, None),
+            };
+            seen.lock().unwrap().push(entry);
+        }
+    }
+
+    fn same(a: &Option<Arc<RwLock<Obj>>>, b: &Arc<RwLock<Obj>>) -> bool {
+        a.as_ref().is_some_and(|a| Arc::ptr_eq(a, b))
+    }
+
+    #[test]
+    fn subscribers_get_each_change_in_order() {
+        let watch = WatchTrie::new(DashTrie::<Obj, NumID>::new());
+        let seen = Seen::default();
+        watch.subscribe(record(&seen));
+        let (one, two, new_one) = (obj(1), obj(2), obj(1));
+        watch.add(one.clone());
+        watch.add(two.clone());
+        watch.add(obj(2));
+        watch.insert(new_one.clone(), true);
+        watch.remove_raw(2);
+        watch.remove_raw(9);
+        watch.dispose();
+
+        let seen = seen.lock().unwrap();
+        let kinds: Vec<(char, Option<u64>)> = seen.iter().map(|(kind, raw_id, ..)| (*kind, *raw_id)).collect();
+        assert_eq!(kinds, vec![('A', Some(1)), ('A', Some(2)), ('R', Some(1)), ('D', Some(2)), ('X', None)]);
+        assert!(same(&seen[0].2, &one) && same(&seen[1].2, &two));
+        assert!(same(&seen[2].2, &new_one) && same(&seen[2].3, &one));
+        assert!(same(&seen[3].2, &two));
+    }
+
+    #[test]
+    fn unsubscribed_get_nothing_more() {
+        let watch = WatchTrie::new(DashTrie::<Obj, NumID>::new());
+        let (gone, kept) = (Seen::default(), Seen::default());
+        let gone_id = watch.subscribe(record(&gone));
+        watch.subscribe(record(&kept));
+        watch.add(obj(1));
+        assert!(watch.unsubscribe(gone_id));
+        assert!(!watch.unsubscribe(gone_id));
+        watch.add(obj(2));
+        watch.remove_raw(1);
+        assert_eq!(gone.lock().unwrap().len(), 1);
+        assert_eq!(kept.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn subscribers_read_the_trie_with_the_change_applied() {
+        let watch = Arc::new(WatchTrie::new(DashTrie::<Obj, NumID>::new()));
+        let reads = Arc::new(Mutex::new(vec![]));
+        let (reader, sink) = (Arc::downgrade(&watch), reads.clone());
+        watch.subscribe(move |event| {
+            let watch = reader.upgrade().unwrap();
+            let raw_id = event.raw_id().unwrap_or(0);
+            sink.lock().unwrap().push((watch.contains_raw(raw_id), watch.size()));
+        });
+        watch.add(obj(1));
+        watch.add(obj(2));
+        watch.remove_raw(1);
+        assert_eq!(*reads.lock().unwrap(), vec![(true, 1), (true, 2), (false, 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "a WatchTrie subscriber changed the trie it watches")]
+    fn subscribers_changing_the_trie_panic() {
+        let watch = Arc::new(WatchTrie::new(DashTrie::<Obj, NumID>::new()));
+        let writer = Arc::downgrade(&watch);
+        watch.subscribe(move |event| {
+            if event.raw_id() == Some(1) {
+                writer.upgrade().unwrap().add(obj(2));
+            }
+        });
+        watch.add(obj(1));
+    }
+
+    #[test]
+    fn a_panicking_subscriber_leaves_the_trie_usable() {
+        let watch = WatchTrie::with_log(DashTrie::<Obj, NumID>::new());
+        let seen = Seen::default();
+        watch.subscribe(|event| assert_ne!(event.raw_id(), Some(1), "subscriber failed"));
+        watch.subscribe(record(&seen));
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| watch.add(obj(1))));
+        assert!(panicked.is_err());
+        assert!(watch.contains_raw(1));
+        assert_eq!(watch.log_len(), 1);
+
+        watch.add(obj(2));
+        watch.add(obj(3));
+        watch.remove_raw(2);
+        assert_eq!(raw_ids(&watch), vec![1, 3]);
+        assert_eq!(watch.log_len(), 4);
+        let kinds: Vec<(char, Option<u64>)> = seen.lock().unwrap().iter().map(|(kind, raw_id, ..)| (*kind, *raw_id)).collect();
+        assert_eq!(kinds, vec![('A', Some(2)), ('A', Some(3)), ('D', Some(2))]);
+    }
+}
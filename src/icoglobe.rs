@@ -66,6 +66,12 @@ pub struct IcoBase {
     uvs: Arc<RwLock<Vec<[f32; 2]>>>,
 }
 
+impl Default for IcoBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IcoBase {
     pub fn new() -> Self {
         Self {
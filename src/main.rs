@@ -1,25 +1,17 @@
-use bevy::ecs::system::StaticSystemInput;
-use bevy::input::{InputPlugin, InputSystem};
-use bevy::input::keyboard::KeyboardInput;
+use b24::hsh::hsh_plugin::HshPlugin;
 use bevy::prelude::*;
-use bevy_rts_camera::{RtsCameraPlugin, RtsCamera};
-use bevy_panorbit_camera::{PanOrbitCameraPlugin, PanOrbitCamera};
+use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
+use bevy_rts_camera::{RtsCamera, RtsCameraControls, RtsCameraPlugin};
 use hexx::*;
 
-mod icoglobe;
-mod vec;
-mod prng;
-mod hsh;
-mod math_trait;
-mod vert_data;
-
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_plugin(RtsCameraPlugin)
-        .add_plugin(PanOrbitCameraPlugin)
-        .add_startup_system(setup)
-        .add_system(switch_camera_mode)
+        .add_plugins(RtsCameraPlugin)
+        .add_plugins(PanOrbitCameraPlugin)
+        .add_plugins(HshPlugin)
+        .add_systems(Startup, setup)
+        .add_systems(Update, switch_camera_mode)
         .run();
 }
 
@@ -37,16 +29,17 @@ enum Mode {
     PanOrbit,
 }
 
-fn setup(mut commands: Commands) {
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
     // Setup RTS Camera
-    commands.spawn((Camera3dBundle {
-        transform: Transform::from_xyz(0.0, 10.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
-        ..default()
-    },
-                    RtsCamera {
-                        ..default()
-                    },
-                    MainCamera,
+    commands.spawn((
+        Transform::from_xyz(0.0, 10.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
+        RtsCamera::default(),
+        RtsCameraControls::default(),
+        MainCamera,
     ));
 
     // Initialize Camera Mode
@@ -56,54 +49,52 @@ fn setup(mut commands: Commands) {
     let layout = HexLayout {
         orientation: HexOrientation::Flat,
         origin: Vec2::ZERO,
-        scale: Default::default(),
+        scale: Vec2::ONE,
     };
-    let hexes = Hex::range(Hex::ZERO, 5);
-    for hex in hexes {
+    let mesh = meshes.add(Cylinder::new(0.1, 0.5));
+    let material = materials.add(Color::srgb(0.8, 0.7, 0.6));
+    for hex in Hex::ZERO.range(5) {
         let pos = layout.hex_to_world_pos(hex);
-        commands.spawn(PbrBundle {
-            mesh: meshes::shape::Cylinder::new(0.1, 0.5).mesh(),
-            material: materials::Color::rgb(0.8, 0.7, 0.6).into(),
-            transform: Transform::from_xyz(pos.x, 0.0, pos.y),
-            ..default()
-        });
+        commands.spawn((
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_xyz(pos.x, 0.0, pos.y),
+        ));
     }
 
     // Spawn a light source
-    commands.spawn(PointLightBundle {
-        point_light: PointLight {
+    commands.spawn((
+        PointLight {
             intensity: 1500.0,
             shadows_enabled: true,
             ..default()
         },
-        transform: Transform::from_xyz(4.0, 8.0, 4.0),
-        ..default()
-    });
+        Transform::from_xyz(4.0, 8.0, 4.0),
+    ));
 }
 
+/// Tab swaps the camera controller, only one may drive the transform
 fn switch_camera_mode(
+    mut commands: Commands,
     mut camera_mode: ResMut<CameraMode>,
-    keyboard_input: Res<StaticSystemInput<KeyboardInput>>,
-    mut rts_cameras: Query<&mut RtsCamera, With<MainCamera>>,
-    mut pan_orbit_cameras: Query<&mut PanOrbitCamera, With<MainCamera>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    cameras: Query<Entity, With<MainCamera>>,
 ) {
-    if keyboard_input.(KeyCode::Tab) {
-        if camera_mode.mode == Mode::Rts {
-            camera_mode.mode = Mode::PanOrbit;
-            if let Ok(mut rts_camera) = rts_cameras.get_single_mut() {
-                rts_camera.enabled = false;
-            }
-            if let Ok(mut pan_orbit_camera) = pan_orbit_cameras.get_single_mut() {
-                pan_orbit_camera.enabled = true;
-            }
-        } else {
-            camera_mode.mode = Mode::Rts;
-            if let Ok(mut rts_camera) = rts_cameras.get_single_mut() {
-                rts_camera.enabled = true;
-            }
-            if let Ok(mut pan_orbit_camera) = pan_orbit_cameras.get_single_mut() {
-                pan_orbit_camera.enabled = false;
-            }
-        }
+    if !keys.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    let Ok(camera) = cameras.get_single() else { return; };
+    if camera_mode.mode == Mode::Rts {
+        camera_mode.mode = Mode::PanOrbit;
+        commands
+            .entity(camera)
+            .remove::<(RtsCamera, RtsCameraControls)>()
+            .insert(PanOrbitCamera::default());
+    } else {
+        camera_mode.mode = Mode::Rts;
+        commands
+            .entity(camera)
+            .remove::<PanOrbitCamera>()
+            .insert((RtsCamera::default(), RtsCameraControls::default()));
     }
 }
@@ -21,9 +21,9 @@
 #![allow(clippy::collection_is_never_read)]
 #![allow(clippy::just_underscores_and_digits)]
 
-use bigdecimal::BigDecimal as Dec;
+use bigdecimal::{num_bigint::Sign, BigDecimal as Dec, Context, RoundingMode, Signed, ToPrimitive, Zero};
 use once_cell::sync::Lazy;
-use std::{marker::PhantomData, ops::Deref, str::FromStr, sync::Arc};
+use std::{marker::PhantomData, num::NonZeroU64, ops::Deref, str::FromStr, sync::Arc};
 use std::num::Wrapping;
 
 const PI_50_DIGITS: &str = "3.14159265358979323846264338327950288419716939937510";
@@ -104,18 +104,18 @@ pub struct Func<'a> {
 impl<'a> Func<'a> {
     fn new() -> Self {
         let c: &Const = CONST.deref();
-        let _0: &Dec = &*c._0;
-        let _1: &Dec = &*c._1;
-        let _2: &Dec = &*c._2;
-        let _3: &Dec = &*c._3;
-        let _4: &Dec = &*c._4;
-        let _5: &Dec = &*c._5;
-        let _6: &Dec = &*c._6;
-        let _7: &Dec = &*c._7;
-        let _8: &Dec = &*c._8;
-        let _9: &Dec = &*c._9;
-        let _a: &Dec = &*c._a;
-        let _b: &Dec = &*c._b;
+        let _0: &Dec = &c._0;
+        let _1: &Dec = &c._1;
+        let _2: &Dec = &c._2;
+        let _3: &Dec = &c._3;
+        let _4: &Dec = &c._4;
+        let _5: &Dec = &c._5;
+        let _6: &Dec = &c._6;
+        let _7: &Dec = &c._7;
+        let _8: &Dec = &c._8;
+        let _9: &Dec = &c._9;
+        let _a: &Dec = &c._a;
+        let _b: &Dec = &c._b;
         Func {
             n_p: Arc::new(-((_1 + _5.sqrt().unwrap()) / _2)),
             phi: Arc::new((_1 + _5.sqrt().unwrap()) / _2),
@@ -148,77 +148,470 @@ pub static FUNC: Lazy<Func<'static>> = Lazy::new(Func::new);
 pub trait Trig {
     fn sin(self) -> Self;
     fn cos(self) -> Self;
+    /// None where cos is 0
+    fn tan(self) -> Option<Self>
+    where
+        Self: Sized;
+    fn asin(self) -> Self;
     fn acos(self) -> Self;
+    fn atan(self) -> Self;
+    /// Angle of the point (x, self)
+    fn atan2(self, x: Self) -> Self;
 }
 
+/// Trig on Dec to PLACES significant digits, through DEC_MATH
 impl Trig for Dec {
     fn sin(self) -> Self {
-        let c: &Const = CONST.deref();
-        let _0: &Dec = &*c._0;
-        let _1: &Dec = &*c._1;
-        let _2: &Dec = &*c._2;
-        let e: &Dec = &*c.epsilon;
+        DEC_MATH.sin(&self)
+    }
+
+    fn cos(self) -> Self {
+        DEC_MATH.cos(&self)
+    }
+
+    fn tan(self) -> Option<Self> {
+        DEC_MATH.tan(&self)
+    }
+
+    fn asin(self) -> Self {
+        DEC_MATH.asin(&self)
+    }
+
+    fn acos(self) -> Self {
+        DEC_MATH.acos(&self)
+    }
+
+    fn atan(self) -> Self {
+        DEC_MATH.atan(&self)
+    }
+
+    fn atan2(self, x: Self) -> Self {
+        DEC_MATH.atan2(&self, &x)
+    }
+}
+
+/// Digits carried past the asked precision
+const GUARD: u64 = 12;
+
+pub static DEC_MATH: Lazy<DecMath> = Lazy::new(|| DecMath::new(PLACES as u64).unwrap());
+
+/// Transcendentals on Dec to a set number of significant digits.<br>
+/// Every step rounds to digits + GUARD and the result is rounded to digits.
+/// Arguments are reduced first, so series only see small values.
+pub struct DecMath {
+    digits: u64,
+    ctx: Context,
+    /// pi and ln2 carry GUARD more digits than ctx,
+    /// so reducing by them keeps every digit up to |x| of 10^GUARD
+    pi: Dec,
+    ln2: Dec,
+}
+
+impl DecMath {
+    /// None if digits is 0
+    pub fn new(digits: u64) -> Option<Self> {
+        let work = NonZeroU64::new(digits)?.get() + GUARD;
+        let wide = context(work + GUARD);
+        let ln2 = atanh_series(&wide.invert(&Dec::from(3)), &wide) * Dec::from(2);
+        Some(DecMath {
+            digits,
+            ctx: context(work),
+            pi: machin_pi(work + GUARD),
+            ln2: wide.round_decimal(ln2),
+        })
+    }
+
+    pub fn digits(&self) -> u64 {
+        self.digits
+    }
+
+    pub fn pi(&self) -> Dec {
+        self.out(&self.pi)
+    }
+
+    pub fn ln2(&self) -> Dec {
+        self.out(&self.ln2)
+    }
+
+    pub fn sin(&self, x: &Dec) -> Dec {
+        self.out(&self.sin_work(x))
+    }
 
-        let s: Arc<Dec> = Arc::new(self);
-        let mut result: Arc<Dec> = Arc::new(_0.clone());
-        let mut term: Arc<Dec> = s.clone();
-        let mut n: Arc<Dec> = Arc::new(_1.clone());
+    pub fn cos(&self, x: &Dec) -> Dec {
+        self.out(&self.cos_work(x))
+    }
+
+    /// None where cos is 0 to working precision
+    pub fn tan(&self, x: &Dec) -> Option<Dec> {
+        let cos = self.cos_work(x);
+        (!cos.is_zero()).then(|| self.out(&self.div(&self.sin_work(x), &cos)))
+    }
 
-        while term.abs() > *e {
-            result = Arc::new((&*result + &*term).clone());
-            n = Arc::new((&*n + _2).clone());
-            term = Arc::new((-&*term * &*s * &*s / (&*n * (&*n - _1))).clone());
+    /// x is clamped to [-1, 1]
+    pub fn asin(&self, x: &Dec) -> Dec {
+        let one = Dec::from(1);
+        if one <= x.abs() {
+            let half_pi = self.pi.half();
+            return self.out(&if x.is_negative() { -half_pi } else { half_pi });
         }
-        (*result).clone()
+        // (1 - x)(1 + x) keeps its digits near |x| = 1, where 1 - x² would not
+        let cos = self.sqrt_work(&self.mul(&(&one - x), &(&one + x)));
+        self.out(&self.atan_work(&self.div(x, &cos)))
     }
 
-    fn cos(self) -> Self {
-        let c: &Const = CONST.deref();
-        let _0: &Dec = &*c._0;
-        let _1: &Dec = &*c._1;
-        let _2: &Dec = &*c._2;
-        let e: &Dec = &*c.epsilon;
+    /// x is clamped to [-1, 1]<br>
+    /// 2 atan(sqrt((1 - x) / (1 + x))) stays precise near both ends,
+    /// where a series in x barely converges.
+    pub fn acos(&self, x: &Dec) -> Dec {
+        let one = Dec::from(1);
+        if &one <= x {
+            return Dec::from(0);
+        }
+        if x <= &-&one {
+            return self.out(&self.pi);
+        }
+        let tan_half = self.sqrt_work(&self.div(&(&one - x), &(&one + x)));
+        self.out(&(self.atan_work(&tan_half) * Dec::from(2)))
+    }
 
-        let s: Arc<Dec> = Arc::new(self);
-        let mut result: Arc<Dec> = Arc::new(_1.clone());
-        let mut term: Arc<Dec> = Arc::new(_1.clone());
-        let mut n: Arc<Dec> = Arc::new(_0.clone());
+    pub fn atan(&self, x: &Dec) -> Dec {
+        self.out(&self.atan_work(x))
+    }
+
+    /// Angle of the point (x, y) in (-pi, pi], 0 at the origin
+    pub fn atan2(&self, y: &Dec, x: &Dec) -> Dec {
+        let half_pi = self.pi.half();
+        let angle = match (x.sign(), y.sign()) {
+            (Sign::NoSign, Sign::NoSign) => Dec::from(0),
+            (Sign::NoSign, Sign::Minus) => -half_pi,
+            (Sign::NoSign, _) => half_pi,
+            (Sign::Plus, _) => self.atan_work(&self.div(y, x)),
+            (Sign::Minus, Sign::Minus) => self.atan_work(&self.div(y, x)) - &self.pi,
+            (Sign::Minus, _) => self.atan_work(&self.div(y, x)) + &self.pi,
+        };
+        self.out(&angle)
+    }
+
+    /// None when the result is too large or small for a Dec
+    pub fn exp(&self, x: &Dec) -> Option<Dec> {
+        self.exp_work(x).map(|exp| self.out(&exp))
+    }
+
+    /// None unless x > 0
+    pub fn ln(&self, x: &Dec) -> Option<Dec> {
+        self.ln_work(x).map(|ln| self.out(&ln))
+    }
 
-        while term.abs() > *e {
-            n = Arc::new((&*n + _2).clone());
-            term = Arc::new((-&*term * &*s * &*s / (&*n * (&*n - _1))).clone());
-            result = Arc::new((&*result + &*term).clone());
+    /// x^y, exact steps for integer y, else exp(y ln x)<br>
+    /// None for a negative x with fractional y, 0 to a power not above 0,
+    /// or a result out of range
+    pub fn pow(&self, x: &Dec, y: &Dec) -> Option<Dec> {
+        if y.is_integer() && let Some(exp) = y.to_i64() {
+            if x.is_zero() && exp <= 0 {
+                return None;
+            }
+            return Some(self.out(&x.powi_with_context(exp, &self.ctx)));
+        }
+        if x.is_zero() {
+            return y.is_positive().then(|| Dec::from(0));
         }
-        (*result).clone()
+        let power = self.exp_work(&self.mul(y, &self.ln_work(x)?))?;
+        Some(self.out(&power))
     }
 
-    fn acos(self) -> Self {
-        let c: &Const = CONST.deref();
-        let _0: &Dec = &*c._0;
-        let _1: &Dec = &*c._1;
-        let _2: &Dec = &*c._2;
-        let pi: &Dec = &*c.pi;
-        let e: &Dec = &*c.epsilon;
-
-        let s: Arc<Dec> = Arc::new(self);
-        let mut term: Arc<Dec> = s.clone();
-        let mut sum: Arc<Dec> = Arc::new((pi / _2.clone() - &*s).clone());
-        let mut n: Arc<Dec> = Arc::new(_1.clone());
-        let x_squared: Arc<Dec> = Arc::new((&*s * &*s).clone());
-
-        while term.abs() > *e {
-            term = Arc::new(
-                (&*term
-                    * &*x_squared
-                    * (&*n * _2.clone() - _1.clone())
-                    * (&*n * _2.clone() - _1.clone())
-                    / ((&*n * _2.clone()) * (&*n * _2.clone() + _1.clone())))
-                .clone(),
-            );
-            sum = Arc::new((&*sum - &*term).clone());
-            n = Arc::new((&*n + _1.clone()).clone());
-        }
-
-        (*sum).clone()
+    /// None if x < 0
+    pub fn sqrt(&self, x: &Dec) -> Option<Dec> {
+        x.sqrt_with_context(&self.ctx).map(|sqrt| self.out(&sqrt))
+    }
+
+    /// with_prec would truncate a negative x, so round explicitly
+    fn out(&self, x: &Dec) -> Dec {
+        x.with_precision_round(NonZeroU64::new(self.digits).unwrap(), RoundingMode::HalfEven)
+            .normalized()
+    }
+
+    fn mul(&self, a: &Dec, b: &Dec) -> Dec {
+        self.ctx.multiply(a, b)
+    }
+
+    fn div(&self, a: &Dec, b: &Dec) -> Dec {
+        self.ctx.multiply(a, &self.ctx.invert(b))
+    }
+
+    fn sqrt_work(&self, x: &Dec) -> Dec {
+        x.sqrt_with_context(&self.ctx).unwrap_or_default()
+    }
+
+    fn work(&self) -> u64 {
+        self.ctx.precision().get()
+    }
+
+    /// x - k 2pi in [-pi, pi]<br>
+    /// Past 10^GUARD, pi is worked out again with a digit for each digit of k.
+    fn reduce(&self, x: &Dec) -> Dec {
+        let magnitude = x.order_of_magnitude().max(0) as u64;
+        let (pi, ctx) = if magnitude < GUARD {
+            (self.pi.clone(), context(self.work() + GUARD))
+        } else {
+            let digits = self.work() + magnitude + GUARD;
+            (machin_pi(digits), context(digits))
+        };
+        let two_pi = pi * Dec::from(2);
+        let turns = ctx
+            .multiply(x, &ctx.invert(&two_pi))
+            .with_scale_round(0, RoundingMode::HalfEven);
+        if turns.is_zero() {
+            return x.clone();
+        }
+        self.ctx.round_decimal(x - turns * two_pi)
+    }
+
+    fn sin_work(&self, x: &Dec) -> Dec {
+        let mut angle = self.reduce(x);
+        // sin(pi - x) = sin(x) folds into [-pi/2, pi/2]
+        let half_pi = self.pi.half();
+        if half_pi < angle {
+            angle = &self.pi - angle;
+        } else if angle < -&half_pi {
+            angle = -&self.pi - angle;
+        }
+        self.sin_series(&angle)
+    }
+
+    fn cos_work(&self, x: &Dec) -> Dec {
+        let angle = self.reduce(x).abs();
+        self.sin_series(&self.ctx.round_decimal(self.pi.half() - angle))
+    }
+
+    /// Taylor series, x in [-pi/2, pi/2]
+    fn sin_series(&self, x: &Dec) -> Dec {
+        let x_squared = self.mul(x, x);
+        let mut term = x.clone();
+        let mut sum = x.clone();
+        let mut n = 1u64;
+        loop {
+            term = -self.div(&self.mul(&term, &x_squared), &Dec::from((n + 1) * (n + 2)));
+            if negligible(&term, &sum, self.work()) {
+                return sum;
+            }
+            sum = self.ctx.round_decimal(sum + &term);
+            n += 2;
+        }
+    }
+
+    /// Folds |x| > 1 over to 1/x, then halves the angle
+    /// with atan(x) = 2 atan(x / (1 + sqrt(1 + x²))) until x < 0.1
+    fn atan_work(&self, x: &Dec) -> Dec {
+        if x.is_zero() {
+            return Dec::from(0);
+        }
+        let one = Dec::from(1);
+        let mut small = x.abs();
+        let folded = one < small;
+        if folded {
+            small = self.ctx.invert(&small);
+        }
+        let mut doublings = 0;
+        while -1 <= small.order_of_magnitude() {
+            let hyp = self.sqrt_work(&(&one + self.mul(&small, &small)));
+            small = self.div(&small, &(&one + hyp));
+            doublings += 1;
+        }
+        let mut angle = self.atan_series(&small) * Dec::from(1u64 << doublings);
+        if folded {
+            angle = self.pi.half() - angle;
+        }
+        let angle = self.ctx.round_decimal(angle);
+        if x.is_negative() { -angle } else { angle }
+    }
+
+    /// Taylor series, |x| < 0.1
+    fn atan_series(&self, x: &Dec) -> Dec {
+        let x_squared = self.mul(x, x);
+        let mut power = x.clone();
+        let mut sum = x.clone();
+        let mut n = 1u64;
+        loop {
+            n += 2;
+            power = -self.mul(&power, &x_squared);
+            let term = self.div(&power, &Dec::from(n));
+            if negligible(&term, &sum, self.work()) {
+                return sum;
+            }
+            sum = self.ctx.round_decimal(sum + term);
+        }
+    }
+
+    /// exp(x) = 2^k exp(r) with r = x - k ln2 in [-ln2/2, ln2/2]
+    fn exp_work(&self, x: &Dec) -> Option<Dec> {
+        if x.is_zero() {
+            return Some(Dec::from(1));
+        }
+        if GUARD as i64 <= x.order_of_magnitude() {
+            return None;
+        }
+        let wide = context(self.work() + GUARD);
+        let halvings = wide
+            .multiply(x, &wide.invert(&self.ln2))
+            .with_scale_round(0, RoundingMode::HalfEven);
+        let rest = self.ctx.round_decimal(x - &halvings * &self.ln2);
+
+        let mut term = Dec::from(1);
+        let mut sum = Dec::from(1);
+        let mut n = 0u64;
+        loop {
+            n += 1;
+            term = self.div(&self.mul(&term, &rest), &Dec::from(n));
+            if negligible(&term, &sum, self.work()) {
+                break;
+            }
+            sum = self.ctx.round_decimal(sum + &term);
+        }
+        let two_k = Dec::from(2).powi_with_context(halvings.to_i64()?, &self.ctx);
+        Some(self.mul(&sum, &two_k))
+    }
+
+    /// ln(x) = k ln2 + ln(m) with m = x / 2^k near 1,
+    /// and ln(m) = 2 atanh((m - 1) / (m + 1))
+    fn ln_work(&self, x: &Dec) -> Option<Dec> {
+        if !x.is_positive() {
+            return None;
+        }
+        // log2 of x from its first 17 digits
+        let (lead, scale) = x.with_prec(17).as_bigint_and_exponent();
+        let log2 = lead.to_f64()?.log2() - scale as f64 * std::f64::consts::LOG2_10;
+        let halvings = log2.round() as i64;
+        let mantissa = self.div(x, &Dec::from(2).powi_with_context(halvings, &self.ctx));
+
+        let one = Dec::from(1);
+        let ratio = self.div(&(&mantissa - &one), &(&mantissa + &one));
+        let ln = Dec::from(halvings) * &self.ln2 + atanh_series(&ratio, &self.ctx) * Dec::from(2);
+        Some(self.ctx.round_decimal(ln))
+    }
+}
+
+fn context(digits: u64) -> Context {
+    Context::new(NonZeroU64::new(digits).unwrap(), RoundingMode::HalfEven)
+}
+
+/// term can no longer change the digits of sum
+fn negligible(term: &Dec, sum: &Dec, digits: u64) -> bool {
+    term.is_zero() || (!sum.is_zero() && term.order_of_magnitude() + (digits as i64) < sum.order_of_magnitude())
+}
+
+/// atanh(x) = x + x³/3 + x⁵/5 + ..., for small |x|
+fn atanh_series(x: &Dec, ctx: &Context) -> Dec {
+    let digits = ctx.precision().get();
+    let x_squared = ctx.multiply(x, x);
+    let mut power = x.clone();
+    let mut sum = x.clone();
+    let mut n = 1u64;
+    loop {
+        n += 2;
+        power = ctx.multiply(&power, &x_squared);
+        let term = ctx.multiply(&power, &ctx.invert(&Dec::from(n)));
+        if negligible(&term, &sum, digits) {
+            return sum;
+        }
+        sum = ctx.round_decimal(sum + term);
+    }
+}
+
+/// atan(1/n) = 1/n - 1/(3n³) + 1/(5n⁵) - ...
+fn atan_inv(n: u64, ctx: &Context) -> Dec {
+    let digits = ctx.precision().get();
+    let inv_n_squared = ctx.invert(&Dec::from(n * n));
+    let mut power = ctx.invert(&Dec::from(n));
+    let mut sum = power.clone();
+    let mut k = 1u64;
+    loop {
+        k += 2;
+        power = -ctx.multiply(&power, &inv_n_squared);
+        let term = ctx.multiply(&power, &ctx.invert(&Dec::from(k)));
+        if negligible(&term, &sum, digits) {
+            return sum;
+        }
+        sum = ctx.round_decimal(sum + term);
+    }
+}
+
+/// Machin's pi = 16 atan(1/5) - 4 atan(1/239), to digits
+fn machin_pi(digits: u64) -> Dec {
+    let ctx = context(digits + 2);
+    let pi = atan_inv(5, &ctx) * Dec::from(16) - atan_inv(239, &ctx) * Dec::from(4);
+    pi.with_prec(digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn math() -> DecMath {
+        DecMath::new(50).unwrap()
+    }
+
+    fn dec(x: &str) -> Dec {
+        Dec::from_str(x).unwrap()
+    }
+
+    /// got must equal the published value rounded to digits,
+    /// negative ones included
+    fn check(math: &DecMath, got: Dec, want: &str) {
+        assert_eq!(got, math.out(&dec(want)), "want {want}");
+    }
+
+    #[test]
+    fn constants() {
+        let math = math();
+        check(&math, math.pi(), "3.1415926535897932384626433832795028841971693993751");
+        check(&math, math.ln2(), "0.69314718055994530941723212145817656807550013436026");
+        check(&math, math.sqrt(&dec("2")).unwrap(), "1.4142135623730950488016887242096980785696718753769");
+    }
+
+    #[test]
+    fn trig_of_large_arguments() {
+        let math = math();
+        check(&math, math.sin(&dec("100")), "-0.50636564110975879365655761045978543206503272129066");
+        check(&math, math.cos(&dec("1e15")), "-0.51319373778697025223453613642316492958815677124475");
+    }
+
+    #[test]
+    fn inverse_trig_near_the_ends() {
+        let math = math();
+        check(&math, math.acos(&dec("0.99999999999999999999")), "1.4142135623730950488028672355116756577770092676309e-10");
+        check(&math, math.acos(&dec("-0.99999999999999999999")), "3.1415926534483718822253338783992161606460018335974");
+        check(&math, math.asin(&dec("0.5")), "0.52359877559829887307710723054658381403286156656252");
+        check(&math, math.asin(&dec("-0.99999999999999999999")), "-1.5707963266534752629940121867594647185474171339099");
+    }
+
+    #[test]
+    fn atan2_in_every_quadrant() {
+        let math = math();
+        check(&math, math.atan2(&dec("2"), &dec("3")), "0.58800260354756755124561108062508542760170724605592");
+        check(&math, math.atan2(&dec("2"), &dec("-3")), "2.5535900500422256872170323026544174565954621533192");
+        check(&math, math.atan2(&dec("-2"), &dec("-3")), "-2.5535900500422256872170323026544174565954621533192");
+        check(&math, math.atan2(&dec("-2"), &dec("3")), "-0.58800260354756755124561108062508542760170724605592");
+        assert_eq!(math.atan2(&dec("0"), &dec("-1")), math.pi());
+    }
+
+    #[test]
+    fn exp_ln_pow() {
+        let math = math();
+        check(&math, math.exp(&dec("50")).unwrap(), "5184705528587072464087.4533229334853848274691005838");
+        check(&math, math.exp(&dec("-50")).unwrap(), "1.9287498479639177830173428165270125747528326512303e-22");
+        check(&math, math.ln(&dec("2")).unwrap(), "0.69314718055994530941723212145817656807550013436026");
+        check(&math, math.ln(&dec("1e-30")).unwrap(), "-69.077552789821370520539743640530926228033044658863");
+        check(&math, math.pow(&dec("2"), &dec("0.5")).unwrap(), "1.4142135623730950488016887242096980785696718753769");
+    }
+
+    #[test]
+    fn undefined_results() {
+        let math = math();
+        assert_eq!(math.pow(&dec("0"), &dec("0")), None);
+        let third = Context::new(NonZeroU64::new(50).unwrap(), RoundingMode::HalfEven).invert(&dec("3"));
+        assert_eq!(math.pow(&dec("-8"), &third), None);
+        assert_eq!(math.ln(&dec("0")), None);
+        assert_eq!(math.sqrt(&dec("-1")), None);
     }
 }
@@ -0,0 +1,265 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::noise::noise_trait::INoise;
+
+/// where each Warp axis samples its warp noise, apart so axes differ
+const WARP_OFFSET: f64 = 5.2;
+
+/// How fractal layers stack: each octave is lacunarity times
+/// the frequency and gain times the amplitude of the one before
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Octaves {
+    pub octaves: u32,
+    pub lacunarity: f64,
+    pub gain: f64,
+}
+
+impl Default for Octaves {
+    fn default() -> Self {
+        Octaves {
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+impl Octaves {
+    /// Sums shape(sample) over the octaves, divided by the total amplitude
+    fn stack<const D: usize>(&self, p: [f64; D], sample: impl Fn([f64; D]) -> f64, shape: impl Fn(f64) -> f64) -> f64 {
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut sum = 0.0;
+        let mut total = 0.0;
+        (0..self.octaves.max(1)).for_each(|_| {
+            sum += amplitude * shape(sample(p.map(|axis| axis * frequency)));
+            total += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        });
+        sum / total
+    }
+}
+
+/// Fractal Brownian motion, plain layered noise
+pub struct Fbm<N: INoise> {
+    pub noise: N,
+    pub octaves: Octaves,
+}
+
+impl<N: INoise> Fbm<N> {
+    pub fn new(noise: N, octaves: Octaves) -> Self {
+        Fbm { noise, octaves }
+    }
+}
+
+impl<N: INoise> INoise for Fbm<N> {
+    fn noise2(&self, p: [f64; 2]) -> f64 {
+        self.octaves.stack(p, |q| self.noise.noise2(q), |value| value)
+    }
+
+    fn noise3(&self, p: [f64; 3]) -> f64 {
+        self.octaves.stack(p, |q| self.noise.noise3(q), |value| value)
+    }
+
+    fn noise4(&self, p: [f64; 4]) -> f64 {
+        self.octaves.stack(p, |q| self.noise.noise4(q), |value| value)
+    }
+}
+
+/// Sharp crests where the noise crosses 0, for mountain ranges
+pub struct Ridged<N: INoise> {
+    pub noise: N,
+    pub octaves: Octaves,
+}
+
+impl<N: INoise> Ridged<N> {
+    pub fn new(noise: N, octaves: Octaves) -> Self {
+        Ridged { noise, octaves }
+    }
+
+    fn ridge(value: f64) -> f64 {
+        let ridge = 1.0 - value.abs();
+        ridge * ridge * 2.0 - 1.0
+    }
+}
+
+impl<N: INoise> INoise for Ridged<N> {
+    fn noise2(&self, p: [f64; 2]) -> f64 {
+        self.octaves.stack(p, |q| self.noise.noise2(q), Self::ridge)
+    }
+
+    fn noise3(&self, p: [f64; 3]) -> f64 {
+        self.octaves.stack(p, |q| self.noise.noise3(q), Self::ridge)
+    }
+
+    fn noise4(&self, p: [f64; 4]) -> f64 {
+        self.octaves.stack(p, |q| self.noise.noise4(q), Self::ridge)
+    }
+}
+
+/// Rounded lumps with creases where the noise crosses 0, for hills and clouds
+pub struct Billow<N: INoise> {
+    pub noise: N,
+    pub octaves: Octaves,
+}
+
+impl<N: INoise> Billow<N> {
+    pub fn new(noise: N, octaves: Octaves) -> Self {
+        Billow { noise, octaves }
+    }
+
+    fn billow(value: f64) -> f64 {
+        value.abs() * 2.0 - 1.0
+    }
+}
+
+impl<N: INoise> INoise for Billow<N> {
+    fn noise2(&self, p: [f64; 2]) -> f64 {
+        self.octaves.stack(p, |q| self.noise.noise2(q), Self::billow)
+    }
+
+    fn noise3(&self, p: [f64; 3]) -> f64 {
+        self.octaves.stack(p, |q| self.noise.noise3(q), Self::billow)
+    }
+
+    fn noise4(&self, p: [f64; 4]) -> f64 {
+        self.octaves.stack(p, |q| self.noise.noise4(q), Self::billow)
+    }
+}
+
+/// Domain warp: samples noise at p moved by amount times warp
+pub struct Warp<N: INoise, W: INoise> {
+    pub noise: N,
+    pub warp: W,
+    pub amount: f64,
+}
+
+impl<N: INoise, W: INoise> Warp<N, W> {
+    pub fn new(noise: N, warp: W, amount: f64) -> Self {
+        Warp { noise, warp, amount }
+    }
+
+    fn warped<const D: usize>(&self, p: [f64; D], warp: impl Fn([f64; D]) -> f64) -> [f64; D] {
+        std::array::from_fn(|axis| {
+            let shift = (axis + 1) as f64 * WARP_OFFSET;
+            p[axis] + self.amount * warp(p.map(|at| at + shift))
+        })
+    }
+}
+
+impl<N: INoise, W: INoise> INoise for Warp<N, W> {
+    fn noise2(&self, p: [f64; 2]) -> f64 {
+        self.noise.noise2(self.warped(p, |q| self.warp.noise2(q)))
+    }
+
+    fn noise3(&self, p: [f64; 3]) -> f64 {
+        self.noise.noise3(self.warped(p, |q| self.warp.noise3(q)))
+    }
+
+    fn noise4(&self, p: [f64; 4]) -> f64 {
+        self.noise.noise4(self.warped(p, |q| self.warp.noise4(q)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::{
+        perlin::Perlin,
+        test_noise::{assert_in_range, assert_in_range_peaking, assert_seeded, points, samples}
+    };
+
+    /// The same value everywhere
+    struct Flat(f64);
+
+    impl INoise for Flat {
+        fn noise2(&self, _: [f64; 2]) -> f64 { self.0 }
+        fn noise3(&self, _: [f64; 3]) -> f64 { self.0 }
+        fn noise4(&self, _: [f64; 4]) -> f64 { self.0 }
+    }
+
+    fn one() -> Octaves {
+        Octaves { octaves: 1, ..Octaves::default() }
+    }
+
+    #[test]
+    fn seeds_draw_their_own_noise() {
+        assert_seeded(|seed| Fbm::new(Perlin::new(seed), Octaves::default()));
+        assert_seeded(|seed| Ridged::new(Perlin::new(seed), Octaves::default()));
+        assert_seeded(|seed| Billow::new(Perlin::new(seed), Octaves::default()));
+        assert_seeded(|seed| Warp::new(Perlin::new(seed), Perlin::new(seed + 100), 0.5));
+    }
+
+    /// Fbm averages octaves that rarely peak together, so it peaks lower
+    #[test]
+    fn stays_in_range() {
+        assert_in_range_peaking(&Fbm::new(Perlin::new(7), Octaves::default()), 0.25);
+        assert_in_range(&Ridged::new(Perlin::new(7), Octaves::default()));
+        assert_in_range(&Billow::new(Perlin::new(7), Octaves::default()));
+        assert_in_range(&Warp::new(Perlin::new(7), Perlin::new(8), 0.5));
+    }
+
+    /// Whatever the octaves and gain, the amplitudes sum to 1
+    #[test]
+    fn normalizes_by_the_total_amplitude() {
+        [Octaves::default(), Octaves { octaves: 3, lacunarity: 3.0, gain: 0.8 }, one()]
+            .into_iter()
+            .for_each(|octaves| {
+                let p = [0.3, 0.6, 0.9];
+                assert!((Fbm::new(Flat(1.0), octaves).noise3(p) - 1.0).abs() < 1e-12);
+                assert!((Fbm::new(Flat(-1.0), octaves).noise3(p) + 1.0).abs() < 1e-12);
+                assert!((Ridged::new(Flat(0.0), octaves).noise3(p) - 1.0).abs() < 1e-12);
+                assert!((Ridged::new(Flat(1.0), octaves).noise3(p) + 1.0).abs() < 1e-12);
+                assert!((Billow::new(Flat(1.0), octaves).noise3(p) - 1.0).abs() < 1e-12);
+                assert!((Billow::new(Flat(0.0), octaves).noise3(p) + 1.0).abs() < 1e-12);
+            });
+    }
+
+    #[test]
+    fn one_octave_is_the_base_noise() {
+        let perlin = Perlin::new(7);
+        let base = samples(&perlin, 500);
+        assert_eq!(samples(&Fbm::new(Perlin::new(7), one()), 500), base);
+        assert_eq!(samples(&Fbm::new(Perlin::new(7), Octaves { octaves: 0, ..one() }), 500), base);
+        let ridged: Vec<f64> = base.iter().map(|value| Ridged::<Perlin>::ridge(*value)).collect();
+        assert_eq!(samples(&Ridged::new(Perlin::new(7), one()), 500), ridged);
+        let billowed: Vec<f64> = base.iter().map(|value| Billow::<Perlin>::billow(*value)).collect();
+        assert_eq!(samples(&Billow::new(Perlin::new(7), one()), 500), billowed);
+    }
+
+    #[test]
+    fn warp_moves_the_sample_point() {
+        let perlin = Perlin::new(7);
+        let still = Warp::new(Perlin::new(7), Perlin::new(8), 0.0);
+        assert_eq!(samples(&still, 500), samples(&perlin, 500));
+
+        let shifted = Warp::new(Perlin::new(7), Flat(0.25), 2.0);
+        points::<3>(500).into_iter().for_each(|p| {
+            assert_eq!(shifted.noise3(p), perlin.noise3(p.map(|axis| axis + 0.5)));
+        });
+
+        let warped = samples(&Warp::new(Perlin::new(7), Perlin::new(8), 0.5), 500);
+        let moved = warped.iter().zip(samples(&perlin, 500)).filter(|(a, b)| **a != *b).count();
+        assert!(moved > 1400, "{moved} of 1500 moved");
+    }
+}
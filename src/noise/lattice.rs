@@ -0,0 +1,88 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::prng::{fast_prng::FastPrng, prng_trait::IPrng};
+
+const SIZE: usize = 256;
+
+/// quintic fade, flat at 0 and 1 so cells join without creases
+pub fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// A shuffled 0 to 255, doubled so chained lookups need no wrap
+pub struct PermTable {
+    perm: [u8; SIZE * 2],
+}
+
+impl PermTable {
+    pub fn new(seed: u64) -> Self {
+        let mut prng = FastPrng::new();
+        prng.seed(seed);
+        Self::from_prng(&mut prng)
+    }
+
+    /// Fisher-Yates with unbiased below
+    pub fn from_prng<P: IPrng>(prng: &mut P) -> Self {
+        let mut shuffled: [u8; SIZE] = std::array::from_fn(|at| at as u8);
+        (1..SIZE).rev().for_each(|at| {
+            let other = prng.below(at as u64 + 1) as usize;
+            shuffled.swap(at, other);
+        });
+        PermTable {
+            perm: std::array::from_fn(|at| shuffled[at % SIZE]),
+        }
+    }
+
+    /// Hash of a lattice corner
+    pub fn hash<const D: usize>(&self, corner: [i32; D]) -> u8 {
+        corner
+            .iter()
+            .fold(0u8, |hash, axis| self.perm[hash as usize + (*axis & 255) as usize])
+    }
+
+    /// Another hash of the same corner, for more than one value per corner
+    pub fn hash_salted<const D: usize>(&self, corner: [i32; D], salt: u8) -> u8 {
+        self.perm[self.hash(corner) as usize + salt as usize]
+    }
+}
+
+/// Blends corner values over the 2^D corners of the cell around p,
+/// corner gets each corner and p relative to it
+pub fn lattice<const D: usize>(p: [f64; D], corner: impl Fn([i32; D], [f64; D]) -> f64) -> f64 {
+    let floor = p.map(f64::floor);
+    let local: [f64; D] = std::array::from_fn(|axis| p[axis] - floor[axis]);
+    let weights = local.map(fade);
+    (0..1usize << D)
+        .map(|bits| {
+            let mut weight = 1.0;
+            let mut at = [0i32; D];
+            let mut offset = [0.0; D];
+            (0..D).for_each(|axis| {
+                let high = bits >> axis & 1 == 1;
+                at[axis] = floor[axis] as i32 + high as i32;
+                offset[axis] = local[axis] - high as u8 as f64;
+                weight *= if high { weights[axis] } else { 1.0 - weights[axis] };
+            });
+            weight * corner(at, offset)
+        })
+        .sum()
+}
@@ -0,0 +1,78 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::vec::dec3::Dec3;
+use num_traits::ToPrimitive;
+
+/// Coherent noise, roughly in [-1, 1] and smooth over its input.<br>
+/// Sample a sphere with noise3 at unit positions so it is seamless.
+pub trait INoise: Send + Sync {
+    fn noise2(&self, p: [f64; 2]) -> f64;
+    fn noise3(&self, p: [f64; 3]) -> f64;
+    fn noise4(&self, p: [f64; 4]) -> f64;
+
+    fn at_dec3(&self, p: &Dec3) -> f64 {
+        let f = |dec: &bigdecimal::BigDecimal| dec.to_f64().unwrap_or_default();
+        self.noise3([f(p.x()), f(p.y()), f(p.z())])
+    }
+
+    /// at an f32 vert like those of IcoBase::verts
+    fn at_vert(&self, vert: [f32; 3]) -> f64 {
+        self.noise3(vert.map(|axis| axis as f64))
+    }
+
+    fn at_verts(&self, verts: &[[f32; 3]]) -> Vec<f64> {
+        verts.iter().map(|vert| self.at_vert(*vert)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::{open_simplex::OpenSimplex, perlin::Perlin, test_noise::points};
+    use bigdecimal::BigDecimal;
+    use std::sync::Arc;
+
+    fn verts() -> Vec<[f32; 3]> {
+        points::<3>(500).into_iter().map(|p| p.map(|axis| (axis / 100.0) as f32)).collect()
+    }
+
+    fn dec3(vert: [f32; 3]) -> Dec3 {
+        let dec = |axis: f32| Arc::new(BigDecimal::try_from(axis as f64).unwrap());
+        Dec3::new(dec(vert[0]), dec(vert[1]), dec(vert[2]))
+    }
+
+    fn agrees<N: INoise>(noise: &N) {
+        let verts = verts();
+        let at_verts = noise.at_verts(&verts);
+        verts.iter().zip(&at_verts).for_each(|(vert, value)| {
+            assert_eq!(noise.at_vert(*vert), *value);
+            assert_eq!(noise.noise3(vert.map(|axis| axis as f64)), *value);
+            assert_eq!(noise.at_dec3(&dec3(*vert)), *value);
+        });
+    }
+
+    #[test]
+    fn verts_and_dec3s_sample_the_f64_path() {
+        agrees(&Perlin::new(7));
+        agrees(&OpenSimplex::new(7));
+    }
+}
@@ -0,0 +1,150 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::{
+    noise::{lattice::PermTable, noise_trait::INoise},
+    prng::prng_trait::IPrng
+};
+
+/// The constants of OpenSimplex in one dimension.<br>
+/// stretch skews p onto the hypercube lattice and squish skews a vertex back.
+/// Gradients have one axis at big and the rest at small, any signs.
+struct Dim {
+    stretch: f64,
+    squish: f64,
+    big: f64,
+    small: f64,
+    norm: f64,
+}
+
+/// (1 / sqrt(3) - 1) / 2 and (sqrt(3) - 1) / 2
+const DIM2: Dim = Dim { stretch: -0.211324865405187, squish: 0.366025403784439, big: 5.0, small: 2.0, norm: 47.0 };
+const DIM3: Dim = Dim { stretch: -1.0 / 6.0, squish: 1.0 / 3.0, big: 11.0, small: 4.0, norm: 103.0 };
+/// (1 / sqrt(5) - 1) / 4 and (sqrt(5) - 1) / 4
+const DIM4: Dim = Dim { stretch: -0.138196601125011, squish: 0.309016994374947, big: 3.0, small: 1.0, norm: 30.0 };
+
+/// OpenSimplex noise, gradient noise on a simplex lattice without
+/// the axis aligned streaks of Perlin.<br>
+/// Each vertex within a radius of sqrt(2) adds (2 - d^2)^4 times its gradient at p.
+pub struct OpenSimplex {
+    perm: PermTable,
+}
+
+impl OpenSimplex {
+    pub fn new(seed: u64) -> Self {
+        OpenSimplex { perm: PermTable::new(seed) }
+    }
+
+    pub fn from_prng<P: IPrng>(prng: &mut P) -> Self {
+        OpenSimplex { perm: PermTable::from_prng(prng) }
+    }
+
+    /// The kernel reaches at most one vertex behind and two ahead
+    /// on each stretched axis, so the 4^D box around the cell holds
+    /// every vertex in reach; those out of reach are skipped before hashing.
+    fn sample<const D: usize>(&self, p: [f64; D], dim: &Dim) -> f64 {
+        let stretch = p.iter().sum::<f64>() * dim.stretch;
+        let cell = p.map(|axis| (axis + stretch).floor() as i32);
+        let value: f64 = (0..1usize << (2 * D))
+            .filter_map(|box_at| {
+                let vert: [i32; D] = std::array::from_fn(|axis| cell[axis] + (box_at >> (2 * axis) & 3) as i32 - 1);
+                let squish = vert.iter().sum::<i32>() as f64 * dim.squish;
+                let delta: [f64; D] = std::array::from_fn(|axis| p[axis] - vert[axis] as f64 - squish);
+                let attn = 2.0 - delta.iter().map(|axis| axis * axis).sum::<f64>();
+                (0.0 < attn).then(|| attn * attn * attn * attn * self.gradient(vert, delta, dim))
+            })
+            .sum();
+        value / dim.norm
+    }
+
+    /// One of D * 2^D gradients, the hash picks the big axis and the signs
+    fn gradient<const D: usize>(&self, vert: [i32; D], delta: [f64; D], dim: &Dim) -> f64 {
+        let pick = self.perm.hash(vert) as usize % (D << D);
+        let big = pick % D;
+        let signs = pick / D;
+        (0..D)
+            .map(|axis| {
+                let size = if axis == big { dim.big } else { dim.small };
+                let grad = if signs >> axis & 1 == 1 { -size } else { size };
+                grad * delta[axis]
+            })
+            .sum()
+    }
+}
+
+impl INoise for OpenSimplex {
+    fn noise2(&self, p: [f64; 2]) -> f64 {
+        self.sample(p, &DIM2)
+    }
+
+    fn noise3(&self, p: [f64; 3]) -> f64 {
+        self.sample(p, &DIM3)
+    }
+
+    fn noise4(&self, p: [f64; 4]) -> f64 {
+        self.sample(p, &DIM4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        noise::test_noise::{assert_continuous, assert_in_range, assert_seeded, samples},
+        prng::fast_prng::FastPrng
+    };
+
+    #[test]
+    fn seeds_draw_their_own_noise() {
+        assert_seeded(OpenSimplex::new);
+    }
+
+    #[test]
+    fn stays_in_range() {
+        assert_in_range(&OpenSimplex::new(7));
+    }
+
+    #[test]
+    fn is_continuous() {
+        assert_continuous(&OpenSimplex::new(7), 10.0);
+    }
+
+    #[test]
+    fn from_prng_matches_new() {
+        let mut prng = FastPrng::new();
+        prng.seed(7);
+        assert_eq!(samples(&OpenSimplex::from_prng(&mut prng), 100), samples(&OpenSimplex::new(7), 100));
+    }
+
+    #[test]
+    fn gradients_cover_every_big_axis_and_sign() {
+        let noise = OpenSimplex::new(7);
+        let delta = [1.0, 10.0, 100.0, 1000.0];
+        // the corners hash to all 256 values, each gradient 4 times
+        let mut grads: Vec<i64> = (0..256)
+            .map(|at| noise.gradient([at, 0, 0, 0], delta, &DIM4) as i64)
+            .collect();
+        grads.sort_unstable();
+        grads.dedup();
+        assert_eq!(grads.len(), 64);
+        assert!(grads.iter().all(|grad| grad.unsigned_abs() <= 3_111));
+    }
+}
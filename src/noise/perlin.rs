@@ -0,0 +1,109 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::{
+    noise::{
+        lattice::{lattice, PermTable},
+        noise_trait::INoise
+    },
+    prng::prng_trait::IPrng
+};
+
+/// The largest raw sum in D dimensions is D / 2.<br>
+/// A corner adds at most the sum of |offset| over the axes,
+/// and blending an axis by fade weighs its |offset| to at most 0.5, at the cell middle.
+/// Dividing by these keeps noise in [-1, 1] without clamping.<br>
+/// Sampled sums peak near 1.0, 1.15 and 1.25, so 3D and 4D noise stays within about +-0.77 and +-0.62.
+const PEAK2: f64 = 1.0;
+const PEAK3: f64 = 1.5;
+const PEAK4: f64 = 2.0;
+
+/// Improved Perlin gradient noise.<br>
+/// Gradients have each axis at +-1, with up to one axis zeroed.
+pub struct Perlin {
+    perm: PermTable,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Perlin { perm: PermTable::new(seed) }
+    }
+
+    pub fn from_prng<P: IPrng>(prng: &mut P) -> Self {
+        Perlin { perm: PermTable::from_prng(prng) }
+    }
+
+    /// peak is the largest raw sum in D dimensions
+    fn sample<const D: usize>(&self, p: [f64; D], peak: f64) -> f64 {
+        let value = lattice(p, |corner, offset| {
+            let hash = self.perm.hash(corner);
+            let zeroed = (hash >> 4) as usize % (D + 1);
+            (0..D)
+                .filter(|axis| *axis != zeroed)
+                .map(|axis| if hash >> axis & 1 == 1 { -offset[axis] } else { offset[axis] })
+                .sum()
+        });
+        value / peak
+    }
+}
+
+impl INoise for Perlin {
+    fn noise2(&self, p: [f64; 2]) -> f64 {
+        self.sample(p, PEAK2)
+    }
+
+    fn noise3(&self, p: [f64; 3]) -> f64 {
+        self.sample(p, PEAK3)
+    }
+
+    fn noise4(&self, p: [f64; 4]) -> f64 {
+        self.sample(p, PEAK4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::test_noise::{assert_continuous, assert_in_range, assert_seeded};
+
+    #[test]
+    fn seeds_draw_their_own_noise() {
+        assert_seeded(Perlin::new);
+    }
+
+    #[test]
+    fn stays_in_range_without_clamping() {
+        assert_in_range(&Perlin::new(7));
+    }
+
+    #[test]
+    fn is_continuous() {
+        assert_continuous(&Perlin::new(7), 10.0);
+    }
+
+    #[test]
+    fn is_0_on_the_lattice() {
+        let perlin = Perlin::new(7);
+        assert_eq!(perlin.noise2([3.0, -4.0]), 0.0);
+        assert_eq!(perlin.noise3([3.0, -4.0, 5.0]), 0.0);
+        assert_eq!(perlin.noise4([3.0, -4.0, 5.0, 6.0]), 0.0);
+    }
+}
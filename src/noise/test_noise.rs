@@ -0,0 +1,94 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::{
+    noise::noise_trait::INoise,
+    prng::{fast_prng::FastPrng, prng_trait::IPrng}
+};
+
+/// Points spread over a few hundred cells, the same for every call
+pub fn points<const D: usize>(count: usize) -> Vec<[f64; D]> {
+    let mut prng = FastPrng::new();
+    prng.seed(17);
+    (0..count)
+        .map(|_| std::array::from_fn(|_| prng.range_f64(-300.0..300.0)))
+        .collect()
+}
+
+/// noise2, noise3 and noise4 at the same points
+pub fn samples<N: INoise>(noise: &N, count: usize) -> Vec<f64> {
+    let two = points::<2>(count).into_iter().map(|p| noise.noise2(p));
+    let three = points::<3>(count).into_iter().map(|p| noise.noise3(p));
+    let four = points::<4>(count).into_iter().map(|p| noise.noise4(p));
+    two.chain(three).chain(four).collect()
+}
+
+/// The same seed draws the same noise and another seed does not
+pub fn assert_seeded<N: INoise>(make: impl Fn(u64) -> N) {
+    let drawn = samples(&make(7), 200);
+    assert_eq!(drawn, samples(&make(7), 200));
+    assert_ne!(drawn, samples(&make(8), 200));
+}
+
+/// Every dimension stays in [-1, 1] and is not flat
+pub fn assert_in_range<N: INoise>(noise: &N) {
+    assert_in_range_peaking(noise, 0.4);
+}
+
+/// Every dimension stays in [-1, 1] and somewhere passes peak
+pub fn assert_in_range_peaking<N: INoise>(noise: &N, peak: f64) {
+    let drawn = samples(noise, 20_000);
+    assert!(drawn.iter().all(|value| (-1.0..=1.0).contains(value)));
+    drawn.chunks(20_000).for_each(|dim| {
+        let max = dim.iter().fold(0.0f64, |max, value| max.max(value.abs()));
+        assert!(peak < max, "peaks at {max}");
+    });
+}
+
+/// A step of step along any axis moves the noise by at most slope times step
+pub fn assert_continuous<N: INoise>(noise: &N, slope: f64) {
+    let step = 1e-4;
+    let near = |a: f64, b: f64| (a - b).abs() <= slope * step;
+    points::<2>(2_000).into_iter().for_each(|p| {
+        let at = noise.noise2(p);
+        (0..2).for_each(|axis| {
+            let mut q = p;
+            q[axis] += step;
+            assert!(near(at, noise.noise2(q)), "noise2 jumps at {p:?}");
+        });
+    });
+    points::<3>(2_000).into_iter().for_each(|p| {
+        let at = noise.noise3(p);
+        (0..3).for_each(|axis| {
+            let mut q = p;
+            q[axis] += step;
+            assert!(near(at, noise.noise3(q)), "noise3 jumps at {p:?}");
+        });
+    });
+    points::<4>(2_000).into_iter().for_each(|p| {
+        let at = noise.noise4(p);
+        (0..4).for_each(|axis| {
+            let mut q = p;
+            q[axis] += step;
+            assert!(near(at, noise.noise4(q)), "noise4 jumps at {p:?}");
+        });
+    });
+}
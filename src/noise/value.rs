@@ -0,0 +1,83 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::{
+    noise::{
+        lattice::{lattice, PermTable},
+        noise_trait::INoise
+    },
+    prng::prng_trait::IPrng
+};
+
+/// Value noise, a random value at each lattice corner blended between them.<br>
+/// Cheaper than Perlin but blockier.
+pub struct ValueNoise {
+    perm: PermTable,
+}
+
+impl ValueNoise {
+    pub fn new(seed: u64) -> Self {
+        ValueNoise { perm: PermTable::new(seed) }
+    }
+
+    pub fn from_prng<P: IPrng>(prng: &mut P) -> Self {
+        ValueNoise { perm: PermTable::from_prng(prng) }
+    }
+
+    fn sample<const D: usize>(&self, p: [f64; D]) -> f64 {
+        lattice(p, |corner, _| self.perm.hash(corner) as f64 / 127.5 - 1.0)
+    }
+}
+
+impl INoise for ValueNoise {
+    fn noise2(&self, p: [f64; 2]) -> f64 {
+        self.sample(p)
+    }
+
+    fn noise3(&self, p: [f64; 3]) -> f64 {
+        self.sample(p)
+    }
+
+    fn noise4(&self, p: [f64; 4]) -> f64 {
+        self.sample(p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::test_noise::{assert_continuous, assert_in_range, assert_seeded};
+
+    #[test]
+    fn seeds_draw_their_own_noise() {
+        assert_seeded(ValueNoise::new);
+    }
+
+    #[test]
+    fn stays_in_range() {
+        assert_in_range(&ValueNoise::new(7));
+    }
+
+    #[test]
+    fn is_continuous() {
+        assert_continuous(&ValueNoise::new(7), 10.0);
+    }
+}
@@ -0,0 +1,116 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::{
+    noise::{lattice::PermTable, noise_trait::INoise},
+    prng::prng_trait::IPrng
+};
+
+/// Worley cellular noise, the distance to the nearest feature point
+/// with one point jittered inside each lattice cell.<br>
+/// Distance 0 to 1 maps to -1 to 1, so cell centers are low
+/// and cell borders are high.
+pub struct Worley {
+    perm: PermTable,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self {
+        Worley { perm: PermTable::new(seed) }
+    }
+
+    pub fn from_prng<P: IPrng>(prng: &mut P) -> Self {
+        Worley { perm: PermTable::from_prng(prng) }
+    }
+
+    /// The nearest feature point, searching the 3^D cells around p
+    pub fn nearest<const D: usize>(&self, p: [f64; D]) -> f64 {
+        let cell = p.map(|axis| axis.floor() as i32);
+        let neighbors = 3usize.pow(D as u32);
+        (0..neighbors)
+            .map(|neighbor| {
+                let mut at = cell;
+                let mut rest = neighbor;
+                (0..D).for_each(|axis| {
+                    at[axis] += (rest % 3) as i32 - 1;
+                    rest /= 3;
+                });
+                (0..D)
+                    .map(|axis| {
+                        let jitter = (self.perm.hash_salted(at, axis as u8) as f64 + 0.5) / 256.0;
+                        let delta = at[axis] as f64 + jitter - p[axis];
+                        delta * delta
+                    })
+                    .sum::<f64>()
+            })
+            .fold(f64::MAX, f64::min)
+            .sqrt()
+    }
+
+    fn sample<const D: usize>(&self, p: [f64; D]) -> f64 {
+        self.nearest(p).min(1.0) * 2.0 - 1.0
+    }
+}
+
+impl INoise for Worley {
+    fn noise2(&self, p: [f64; 2]) -> f64 {
+        self.sample(p)
+    }
+
+    fn noise3(&self, p: [f64; 3]) -> f64 {
+        self.sample(p)
+    }
+
+    fn noise4(&self, p: [f64; 4]) -> f64 {
+        self.sample(p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::test_noise::{assert_continuous, assert_in_range, assert_seeded};
+
+    #[test]
+    fn seeds_draw_their_own_noise() {
+        assert_seeded(Worley::new);
+    }
+
+    #[test]
+    fn stays_in_range() {
+        assert_in_range(&Worley::new(7));
+    }
+
+    #[test]
+    fn is_continuous() {
+        assert_continuous(&Worley::new(7), 10.0);
+    }
+
+    #[test]
+    fn is_low_at_its_feature_points() {
+        let worley = Worley::new(7);
+        let cell = [3, -4];
+        let jitter = |axis: usize| (worley.perm.hash_salted(cell, axis as u8) as f64 + 0.5) / 256.0;
+        let point = [cell[0] as f64 + jitter(0), cell[1] as f64 + jitter(1)];
+        assert_eq!(worley.nearest(point), 0.0);
+        assert_eq!(worley.noise2(point), -1.0);
+    }
+}
@@ -0,0 +1,361 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::{
+    icoglobe::IcoBase,
+    prng::{fast_prng::FastPrng, prng_trait::IPrng}
+};
+use std::f64::consts::{PI, TAU};
+
+type P3 = [f64; 3];
+
+/// candidates tried around each active sample before it retires
+const TRIES: u32 = 30;
+/// deepest face subdivision, 20 * 4^8 leaves
+const MAX_DEPTH: u32 = 8;
+/// angle across an icosahedron face edge, about 63.4 degrees
+const ICO_EDGE: f64 = 1.1071487177940904;
+
+fn dot(a: &P3, b: &P3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &P3, b: &P3) -> P3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(p: P3) -> P3 {
+    let length = dot(&p, &p).sqrt();
+    p.map(|axis| axis / length)
+}
+
+/// angle between unit vectors
+fn angle(a: &P3, b: &P3) -> f64 {
+    dot(a, b).clamp(-1.0, 1.0).acos()
+}
+
+/// One face of the subdivided icosahedron.<br>
+/// cap is the angle from center that covers the face and everything
+/// stored under it, it grows if a point lands past the face edge.<br>
+/// kids are split off on the first insert, so empty faces cost nothing.
+struct Face {
+    verts: [P3; 3],
+    center: P3,
+    cap: f64,
+    depth: u32,
+    kids: Vec<Face>,
+    items: Vec<(usize, P3)>,
+}
+
+impl Face {
+    fn new(verts: [P3; 3], depth: u32) -> Self {
+        let center = normalize([0, 1, 2].map(|axis| verts.iter().map(|vert| vert[axis]).sum()));
+        let cap = verts.iter().map(|vert| angle(&center, vert)).fold(0.0, f64::max);
+        Face { verts, center, cap, depth, kids: vec![], items: vec![] }
+    }
+
+    fn split(&mut self) {
+        let verts = self.verts;
+        let mid = |a: usize, b: usize| {
+            normalize([0, 1, 2].map(|axis| verts[a][axis] + verts[b][axis]))
+        };
+        let (ab, bc, ca) = (mid(0, 1), mid(1, 2), mid(2, 0));
+        self.kids = vec![
+            Face::new([verts[0], ab, ca], self.depth - 1),
+            Face::new([ab, verts[1], bc], self.depth - 1),
+            Face::new([ca, bc, verts[2]], self.depth - 1),
+            Face::new([ab, bc, ca], self.depth - 1),
+        ];
+    }
+
+    fn insert(&mut self, index: usize, p: P3) {
+        self.cap = self.cap.max(angle(&self.center, &p));
+        if 0 == self.depth {
+            self.items.push((index, p));
+            return;
+        }
+        if self.kids.is_empty() {
+            self.split();
+        }
+        if let Some(kid) = self.kids.iter_mut().max_by(|a, b| dot(&a.center, &p).total_cmp(&dot(&b.center, &p))) {
+            kid.insert(index, p);
+        }
+    }
+
+    fn any_within(&self, p: &P3, radius: f64) -> bool {
+        if radius < angle(&self.center, p) - self.cap {
+            return false;
+        }
+        self.items.iter().any(|(_, item)| angle(item, p) < radius)
+            || self.kids.iter().any(|kid| kid.any_within(p, radius))
+    }
+
+    fn nearest(&self, p: &P3, best: &mut Option<(usize, f64)>) {
+        if let Some((_, best_angle)) = best
+            && *best_angle < angle(&self.center, p) - self.cap
+        {
+            return;
+        }
+        self.items.iter().for_each(|(index, item)| {
+            let item_angle = angle(item, p);
+            if best.is_none_or(|(_, best_angle)| item_angle < best_angle) {
+                *best = Some((*index, item_angle));
+            }
+        });
+        let mut kids: Vec<&Face> = self.kids.iter().collect();
+        kids.sort_by(|a, b| dot(&b.center, p).total_cmp(&dot(&a.center, p)));
+        kids.into_iter().for_each(|kid| kid.nearest(p, best));
+    }
+}
+
+/// Points on the unit sphere indexed by the face hierarchy of the icoglobe,
+/// so a neighbor search only visits faces near the point
+pub struct FaceTree {
+    roots: Vec<Face>,
+    len: usize,
+}
+
+impl FaceTree {
+    /// The 20 IcoBase faces, each split into 4 depth times
+    pub fn new(depth: u32) -> Self {
+        let ico = IcoBase::new();
+        let verts: Vec<P3> = ico.verts()
+            .read()
+            .unwrap()
+            .iter()
+            .map(|vert| normalize(vert.map(|axis| axis as f64)))
+            .collect();
+        let roots = ico.tris()
+            .read()
+            .unwrap()
+            .chunks_exact(3)
+            .map(|tri| Face::new([0, 1, 2].map(|at| verts[tri[at] as usize]), depth.min(MAX_DEPTH)))
+            .collect();
+        FaceTree { roots, len: 0 }
+    }
+
+    /// Deep enough that leaf faces are about radius across
+    pub fn for_radius(radius: f64) -> Self {
+        let depth = (ICO_EDGE / radius).log2().ceil().max(0.0) as u32;
+        Self::new(depth)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// p must be unit length
+    pub fn insert(&mut self, index: usize, p: P3) {
+        if let Some(root) = self.roots
+            .iter_mut()
+            .max_by(|a, b| dot(&a.center, &p).total_cmp(&dot(&b.center, &p)))
+        {
+            root.insert(index, p);
+            self.len += 1;
+        }
+    }
+
+    /// Is any point closer to p than radius, an angle
+    pub fn any_within(&self, p: &P3, radius: f64) -> bool {
+        self.roots.iter().any(|root| root.any_within(p, radius))
+    }
+
+    /// The index of the point closest to p, None if empty
+    pub fn nearest(&self, p: &P3) -> Option<usize> {
+        let mut best = None;
+        let mut roots: Vec<&Face> = self.roots.iter().collect();
+        roots.sort_by(|a, b| dot(&b.center, p).total_cmp(&dot(&a.center, p)));
+        roots.into_iter().for_each(|root| root.nearest(p, &mut best));
+        best.map(|(index, _)| index)
+    }
+}
+
+/// Bridson's Poisson-disc sampling on the unit sphere:
+/// no two samples closer than min_angle, and no gap wide enough for another.<br>
+/// Good for placing cities, resources and trees evenly but not on a grid.
+pub struct PoissonDisc {
+    min_angle: f64,
+    tries: u32,
+}
+
+impl PoissonDisc {
+    /// min_angle in radians, None unless between 0 and pi
+    pub fn new(min_angle: f64) -> Option<Self> {
+        if !(0.0 < min_angle && min_angle < PI) {
+            return None;
+        }
+        Some(PoissonDisc { min_angle, tries: TRIES })
+    }
+
+    /// More tries pack samples tighter but run slower
+    pub fn with_tries(mut self, tries: u32) -> Self {
+        self.tries = tries.max(1);
+        self
+    }
+
+    pub fn min_angle(&self) -> f64 {
+        self.min_angle
+    }
+
+    pub fn sample(&self, seed: u64) -> Vec<P3> {
+        let mut prng = FastPrng::new();
+        prng.seed(seed);
+        self.sample_with(&mut prng)
+    }
+
+    pub fn sample_with<P: IPrng>(&self, prng: &mut P) -> Vec<P3> {
+        let mut tree = FaceTree::for_radius(self.min_angle);
+        let mut samples = vec![Self::uniform(prng)];
+        let mut active = vec![0];
+        tree.insert(0, samples[0]);
+        let (cos_near, cos_far) = (self.min_angle.cos(), (2.0 * self.min_angle).min(PI).cos());
+        while !active.is_empty() {
+            let slot = prng.below(active.len() as u64) as usize;
+            let center = samples[active[slot]];
+            let found = (0..self.tries)
+                .map(|_| Self::around(prng, &center, cos_far, cos_near))
+                .find(|candidate| !tree.any_within(candidate, self.min_angle));
+            match found {
+                Some(candidate) => {
+                    tree.insert(samples.len(), candidate);
+                    active.push(samples.len());
+                    samples.push(candidate);
+                }
+                None => {
+                    active.swap_remove(slot);
+                }
+            }
+        }
+        samples
+    }
+
+    /// For each sample, the index of the closest globe cell center
+    pub fn nearest_cells(samples: &[P3], cells: &[[f32; 3]]) -> Vec<Option<usize>> {
+        let radius = (4.0 * PI / cells.len().max(1) as f64).sqrt();
+        let mut tree = FaceTree::for_radius(radius);
+        cells.iter().enumerate().for_each(|(index, cell)| {
+            tree.insert(index, normalize(cell.map(|axis| axis as f64)));
+        });
+        samples.iter().map(|sample| tree.nearest(sample)).collect()
+    }
+
+    fn uniform<P: IPrng>(prng: &mut P) -> P3 {
+        let z = prng.range_f64(-1.0..1.0);
+        let around = prng.f64() * TAU;
+        let ring = (1.0 - z * z).sqrt();
+        [ring * around.cos(), ring * around.sin(), z]
+    }
+
+    /// A point at an angle from center between the cosines,
+    /// uniform over the area of that ring
+    fn around<P: IPrng>(prng: &mut P, center: &P3, cos_far: f64, cos_near: f64) -> P3 {
+        let axis = if center[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+        let tangent = normalize(cross(center, &axis));
+        let bitangent = cross(center, &tangent);
+        let cos_off = if cos_far < cos_near { prng.range_f64(cos_far..cos_near) } else { cos_near };
+        let sin_off = (1.0 - cos_off * cos_off).max(0.0).sqrt();
+        let turn = prng.f64() * TAU;
+        let (sin_turn, cos_turn) = turn.sin_cos();
+        normalize([0, 1, 2].map(|at| {
+            center[at] * cos_off + sin_off * (tangent[at] * cos_turn + bitangent[at] * sin_turn)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn faces(face: &Face) -> usize {
+        1 + face.kids.iter().map(faces).sum::<usize>()
+    }
+
+    fn tree_faces(tree: &FaceTree) -> usize {
+        tree.roots.iter().map(faces).sum()
+    }
+
+    #[test]
+    fn faces_split_on_insert() {
+        let mut tree = FaceTree::new(MAX_DEPTH);
+        assert_eq!(tree_faces(&tree), 20);
+        tree.insert(0, [0.0, 0.0, 1.0]);
+        assert_eq!(tree_faces(&tree), 20 + 4 * MAX_DEPTH as usize);
+    }
+
+    #[test]
+    fn deep_tree_matches_brute_force() {
+        let mut prng = FastPrng::new();
+        prng.seed(5);
+        let points: Vec<P3> = (0..500).map(|_| PoissonDisc::uniform(&mut prng)).collect();
+        let mut tree = FaceTree::new(MAX_DEPTH);
+        assert!(tree.nearest(&points[0]).is_none());
+        points.iter().enumerate().for_each(|(index, point)| tree.insert(index, *point));
+        assert_eq!(tree.len(), 500);
+        (0..200).for_each(|_| {
+            let p = PoissonDisc::uniform(&mut prng);
+            let angles: Vec<f64> = points.iter().map(|point| angle(point, &p)).collect();
+            let closest = (0..angles.len()).min_by(|a, b| angles[*a].total_cmp(&angles[*b])).unwrap();
+            assert_eq!(tree.nearest(&p), Some(closest));
+            assert_eq!(tree.any_within(&p, 0.05), angles[closest] < 0.05);
+        });
+    }
+
+    #[test]
+    fn nearest_cells_match_brute_force() {
+        let mut prng = FastPrng::new();
+        prng.seed(11);
+        // cells need not be unit length, like scaled globe verts
+        let cells: Vec<[f32; 3]> = (0..300)
+            .map(|_| PoissonDisc::uniform(&mut prng).map(|axis| (axis * 2.5) as f32))
+            .collect();
+        let mut samples = PoissonDisc::new(0.3).unwrap().sample(3);
+        samples.extend((0..200).map(|_| PoissonDisc::uniform(&mut prng)));
+        let nearest = PoissonDisc::nearest_cells(&samples, &cells);
+        assert_eq!(nearest.len(), samples.len());
+        samples.iter().zip(&nearest).for_each(|(sample, found)| {
+            let angles: Vec<f64> = cells
+                .iter()
+                .map(|cell| angle(&normalize(cell.map(|axis| axis as f64)), sample))
+                .collect();
+            let closest = (0..angles.len()).min_by(|a, b| angles[*a].total_cmp(&angles[*b])).unwrap();
+            assert_eq!(*found, Some(closest));
+        });
+        assert!(PoissonDisc::nearest_cells(&samples, &[]).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn samples_keep_their_distance() {
+        let disc = PoissonDisc::new(0.2).unwrap();
+        let samples = disc.sample(9);
+        assert!(100 < samples.len());
+        samples.iter().enumerate().for_each(|(at, a)| {
+            samples[at + 1..].iter().for_each(|b| assert!(disc.min_angle() <= angle(a, b)));
+        });
+    }
+}
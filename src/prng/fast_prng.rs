@@ -21,8 +21,15 @@
 use std::num::Wrapping;
 use std::ops::Deref;
 use num_traits::FromPrimitive;
+use rand::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
 use crate::math_trait::{Const, CONST};
+use crate::prng::prng_trait::IPrng;
 
+/// The whole state, stack included, is saved by serde or to_bytes.<br>
+/// Both refuse to load an even bud, as with_bud does.
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "FastPrngSer")]
 pub struct FastPrng {
     lo: Wrapping<u64>,
     hi: Wrapping<u64>,
@@ -30,11 +37,87 @@ pub struct FastPrng {
     bud: Wrapping<u64>,
 }
 
+/// Loaded form of a FastPrng, checked before it becomes one
+#[derive(Deserialize)]
+struct FastPrngSer {
+    lo: u64,
+    hi: u64,
+    prng_stack: Vec<PrngInst>,
+    bud: u64,
+}
+
+impl TryFrom<FastPrngSer> for FastPrng {
+    type Error = String;
+
+    fn try_from(ser: FastPrngSer) -> Result<Self, Self::Error> {
+        if ser.bud.is_multiple_of(2) {
+            return Err(format!("FastPrng bud {:#x} is even", ser.bud));
+        }
+        Ok(FastPrng {
+            lo: Wrapping(ser.lo),
+            hi: Wrapping(ser.hi),
+            prng_stack: ser.prng_stack,
+            bud: Wrapping(ser.bud),
+        })
+    }
+}
+
 const SHIFT:u32 = 17;
+
+/// 2^64 / golden ratio, the odd constant every multiplicative hash here shares
+pub const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Vetted buds, each passes prng_stats::report at 1e-6
+/// for seeds 1 to 40 over 100000 draws.<br>
+/// Classic is the CONST.bud every FastPrng::new uses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Bud {
+    Classic,
+    /// 2^64 / golden ratio
+    Golden,
+    /// alternating bits, the old commented out CONST.bud
+    Alternating,
+    /// fraction of pi
+    Pi,
+    /// fraction of e
+    E,
+    /// fraction of the square root of 2
+    Sqrt2,
+}
+
+impl Bud {
+    pub const ALL: [Bud; 6] = [Bud::Classic, Bud::Golden, Bud::Alternating, Bud::Pi, Bud::E, Bud::Sqrt2];
+
+    pub fn value(self) -> u64 {
+        match self {
+            Bud::Classic => CONST.bud.0,
+            Bud::Golden => GOLDEN_GAMMA,
+            Bud::Alternating => 0x5555_5555_5555_5555,
+            Bud::Pi => 0x243F_6A88_85A3_08D3,
+            Bud::E => 0xB7E1_5162_8AED_2A6B,
+            Bud::Sqrt2 => 0x6A09_E667_F3BC_C909,
+        }
+    }
+}
+
+/// splitmix64 finalizer, spreads every input bit over the output.<br>
+/// The one mixer for seeds, keys and filters alike.
+pub const fn split_mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+impl Default for FastPrng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FastPrng {
     pub fn new() -> Self {
         let c: &Const = CONST.deref();
-        let b = c.bud.clone().deref().clone();
+        let b = *c.bud.deref();
         FastPrng {
             lo: b,
             hi: !b,
@@ -43,6 +126,30 @@ impl FastPrng {
         }
     }
     
+    /// A family of streams apart from the CONST.bud one<br>
+    /// None for an even bud, which skips half the states; see Bud for vetted ones
+    pub fn with_bud(bud: u64, seed: u64) -> Option<Self> {
+        if bud.is_multiple_of(2) {
+            return None;
+        }
+        let mut prng = FastPrng {
+            lo: Wrapping(bud),
+            hi: !Wrapping(bud),
+            prng_stack: vec![],
+            bud: Wrapping(bud),
+        };
+        prng.seed(seed);
+        Some(prng)
+    }
+
+    pub fn from_bud(bud: Bud, seed: u64) -> Self {
+        Self::with_bud(bud.value(), seed).unwrap()
+    }
+
+    pub fn bud(&self) -> u64 {
+        self.bud.0
+    }
+
     pub fn seed(&mut self, seed: u64) {
         if 0 != seed {
             self.lo = Wrapping::from_u64(seed).unwrap();
@@ -68,30 +175,368 @@ impl FastPrng {
     pub fn clear_stack(&mut self) {
         self.prng_stack.clear();
     }
-    
-    pub fn u64(&mut self) -> u64 {
-        self.hi = self.hi.rotate_left(SHIFT) + self.hi.rotate_right(SHIFT) + self.lo;
+
+    /// A stream that depends only on seed and key,
+    /// so a chunk keyed by its index draws the same on any thread
+    pub fn keyed(seed: u64, key: u64) -> Self {
+        let mut prng = FastPrng::new();
+        prng.set_state(split_mix(seed ^ split_mix(key)), split_mix(!seed ^ split_mix(!key)));
+        prng
+    }
+
+    /// keyed for the family of with_bud, None for an even bud
+    pub fn keyed_with_bud(bud: u64, seed: u64, key: u64) -> Option<Self> {
+        let mut prng = FastPrng::with_bud(bud, 0)?;
+        prng.set_state(split_mix(seed ^ split_mix(key)), split_mix(!seed ^ split_mix(!key)));
+        Some(prng)
+    }
+
+    /// Like keyed, from this stream's current state, which is left as is
+    pub fn fork(&self, key: u64) -> Self {
+        let lo = split_mix(self.lo.0 ^ split_mix(key));
+        let hi = split_mix(self.hi.0 ^ split_mix(!key));
+        self.sibling(lo, hi)
+    }
+
+    /// A new stream seeded from the next two draws of this one
+    pub fn split(&mut self) -> Self {
+        let lo = split_mix(self.u64());
+        let hi = split_mix(self.u64());
+        self.sibling(lo, hi)
+    }
+
+    /// A stream of this bud at lo and hi,
+    /// the bud is set first so an all zero state falls back to it
+    fn sibling(&self, lo: u64, hi: u64) -> Self {
+        let mut prng = FastPrng {
+            lo: self.bud,
+            hi: !self.bud,
+            prng_stack: vec![],
+            bud: self.bud,
+        };
+        prng.set_state(lo, hi);
+        prng
+    }
+
+    /// Skips n draws<br>
+    /// O(n), the rotations make u64 nonlinear so there is no shortcut;
+    /// use keyed or fork for streams far apart.
+    pub fn jump(&mut self, n: u64) {
+        (0..n).for_each(|_| {
+            self.u64();
+        });
+    }
+
+    /// lo, hi, bud, the stack length then each stacked lo and hi,
+    /// all little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(28 + self.prng_stack.len() * 16);
+        bytes.extend_from_slice(&self.lo.0.to_le_bytes());
+        bytes.extend_from_slice(&self.hi.0.to_le_bytes());
+        bytes.extend_from_slice(&self.bud.0.to_le_bytes());
+        bytes.extend_from_slice(&(self.prng_stack.len() as u32).to_le_bytes());
+        self.prng_stack.iter().for_each(|inst| {
+            bytes.extend_from_slice(&inst.lo.to_le_bytes());
+            bytes.extend_from_slice(&inst.hi.to_le_bytes());
+        });
+        bytes
+    }
+
+    /// None if bytes is not exactly what to_bytes makes or the bud is even
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let u64_at = |at: usize| Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?));
+        let stack_len = u32::from_le_bytes(bytes.get(24..28)?.try_into().ok()?) as usize;
+        if bytes.len() != 28 + stack_len * 16 || u64_at(16)?.is_multiple_of(2) {
+            return None;
+        }
+        let prng_stack = (0..stack_len)
+            .map(|inst| {
+                let at = 28 + inst * 16;
+                Some(PrngInst { lo: u64_at(at)?, hi: u64_at(at + 8)? })
+            })
+            .collect::<Option<Vec<PrngInst>>>()?;
+        Some(FastPrng {
+            lo: Wrapping(u64_at(0)?),
+            hi: Wrapping(u64_at(8)?),
+            prng_stack,
+            bud: Wrapping(u64_at(16)?),
+        })
+    }
+
+    /// An all zero state falls back to the bud like seed(0)
+    fn set_state(&mut self, lo: u64, hi: u64) {
+        if 0 != lo || 0 != hi {
+            self.lo = Wrapping(lo);
+            self.hi = Wrapping(hi);
+        }
+        else {
+            self.seed(0);
+        }
+    }
+}
+
+impl IPrng for FastPrng {
+    fn u64(&mut self) -> u64 {
+        self.hi = Wrapping(self.hi.0.rotate_left(SHIFT)) + Wrapping(self.hi.0.rotate_right(SHIFT)) + self.lo;
         self.lo += self.hi + self.bud;
         self.hi.0
     }
-    
-    pub fn u8(&mut self) -> u8 {
-        let max = u8::MAX as f64;
-        (self.f64() * max).round() as u8
+}
+
+/// Lets rand and rand_distr draw from a FastPrng stream,
+/// e.g. Normal::sample or SliceRandom::shuffle
+impl RngCore for FastPrng {
+    /// the high half, the low bits of u64 are weaker
+    fn next_u32(&mut self) -> u32 {
+        (self.u64() >> 32) as u32
     }
-    
-    ///  Random number from 0 to 1
-    pub fn f64(&mut self) -> f64 {
-        (self.u64() / 2) as f64 / (u64::MAX / 2) as f64
+
+    fn next_u64(&mut self) -> u64 {
+        self.u64()
     }
 
-    ///  Random number from 0 to 1
-    pub fn f32(&mut self) -> f32 {
-        self.f64() as f32
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        dst.chunks_mut(8).for_each(|chunk| {
+            let bytes = self.u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        });
     }
 }
 
+impl SeedableRng for FastPrng {
+    /// lo then hi, little endian
+    type Seed = [u8; 16];
+
+    /// An all zero seed falls back to the bud like seed(0)
+    fn from_seed(seed: Self::Seed) -> Self {
+        let mut prng = FastPrng::new();
+        let lo = u64::from_le_bytes(seed[..8].try_into().unwrap());
+        let hi = u64::from_le_bytes(seed[8..].try_into().unwrap());
+        prng.set_state(lo, hi);
+        prng
+    }
+
+    /// Same stream as seed(state)
+    fn seed_from_u64(state: u64) -> Self {
+        let mut prng = FastPrng::new();
+        prng.seed(state);
+        prng
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 struct PrngInst {
     lo: u64,
     hi: u64,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prng::prng_stats::report;
+    use rand::{seq::SliceRandom, Rng};
+    use rand_distr::{Distribution, Normal};
+    use rayon::prelude::*;
+
+    /// An independent copy, FastPrng is not Clone
+    fn copy(prng: &FastPrng) -> FastPrng {
+        FastPrng::from_bytes(&prng.to_bytes()).unwrap()
+    }
+
+    #[test]
+    fn fork_and_split_keep_the_bud() {
+        let mut prng = FastPrng::from_bud(Bud::Golden, 7);
+        assert_eq!(prng.fork(3).bud(), GOLDEN_GAMMA);
+        assert_eq!(prng.split().bud(), GOLDEN_GAMMA);
+    }
+
+    #[test]
+    fn fork_to_zero_falls_back_to_own_bud() {
+        let key = 5;
+        let mut bytes = split_mix(key).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&split_mix(!key).to_le_bytes());
+        bytes.extend_from_slice(&GOLDEN_GAMMA.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        let prng = FastPrng::from_bytes(&bytes).unwrap();
+        let mut fork = prng.fork(key);
+        let mut golden = FastPrng::from_bud(Bud::Golden, 0);
+        (0..8).for_each(|_| assert_eq!(fork.u64(), golden.u64()));
+    }
+
+    #[test]
+    fn classic_is_the_const_bud() {
+        assert_eq!(Bud::Classic.value(), FastPrng::new().bud());
+        assert!(Bud::ALL.iter().all(|bud| bud.value() % 2 == 1));
+    }
+
+    #[test]
+    fn vetted_buds_pass_the_report() {
+        Bud::ALL.par_iter().for_each(|bud| {
+            (1..=40).for_each(|seed| {
+                let report = report(&mut FastPrng::from_bud(*bud, seed), 100_000);
+                assert!(report.passes(1e-6), "{bud:?} seed {seed}: {report}");
+            });
+        });
+    }
+
+    #[test]
+    fn keyed_with_bud_keeps_the_bud() {
+        assert!(FastPrng::keyed_with_bud(2, 1, 1).is_none());
+        let mut a = FastPrng::keyed_with_bud(GOLDEN_GAMMA, 11, 12).unwrap();
+        let mut b = FastPrng::keyed(11, 12);
+        assert_eq!(a.bud(), GOLDEN_GAMMA);
+        assert_ne!((0..8).map(|_| a.u64()).collect::<Vec<u64>>(), (0..8).map(|_| b.u64()).collect::<Vec<u64>>());
+        let mut c = FastPrng::keyed_with_bud(CONST.bud.0, 11, 12).unwrap();
+        let mut d = FastPrng::keyed(11, 12);
+        (0..8).for_each(|_| assert_eq!(c.u64(), d.u64()));
+    }
+
+    #[test]
+    fn keyed_is_deterministic() {
+        let mut a = FastPrng::keyed(11, 12);
+        let mut b = FastPrng::keyed(11, 12);
+        let mut c = FastPrng::keyed(11, 13);
+        let drawn: Vec<u64> = (0..8).map(|_| a.u64()).collect();
+        assert_eq!(drawn, (0..8).map(|_| b.u64()).collect::<Vec<u64>>());
+        assert_ne!(drawn, (0..8).map(|_| c.u64()).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn from_seed_is_lo_then_hi_little_endian() {
+        let (lo, hi) = (0x0123_4567_89AB_CDEFu64, 0xFEDC_BA98_7654_3210u64);
+        let mut seed = [0u8; 16];
+        seed[..8].copy_from_slice(&lo.to_le_bytes());
+        seed[8..].copy_from_slice(&hi.to_le_bytes());
+        let prng = FastPrng::from_seed(seed);
+        assert_eq!(prng.to_bytes()[..16], seed);
+
+        let mut state = seed.to_vec();
+        state.extend_from_slice(&CONST.bud.0.to_le_bytes());
+        state.extend_from_slice(&0u32.to_le_bytes());
+        let mut by_state = FastPrng::from_bytes(&state).unwrap();
+        let mut by_seed = prng;
+        (0..8).for_each(|_| assert_eq!(by_seed.next_u64(), by_state.u64()));
+    }
+
+    #[test]
+    fn zero_seeds_fall_back_to_the_bud() {
+        let mut zero = FastPrng::from_seed([0u8; 16]);
+        let mut bud = FastPrng::new();
+        bud.seed(0);
+        (0..8).for_each(|_| assert_eq!(zero.u64(), bud.u64()));
+        let mut by_u64 = FastPrng::seed_from_u64(7);
+        let mut seeded = FastPrng::new();
+        seeded.seed(7);
+        (0..8).for_each(|_| assert_eq!(by_u64.u64(), seeded.u64()));
+    }
+
+    #[test]
+    fn next_u32_is_the_high_half() {
+        let mut prng = FastPrng::seed_from_u64(7);
+        let mut same = copy(&prng);
+        (0..64).for_each(|_| assert_eq!(prng.next_u32(), (same.u64() >> 32) as u32));
+    }
+
+    #[test]
+    fn fill_bytes_takes_little_endian_draws() {
+        let mut prng = FastPrng::seed_from_u64(7);
+        let mut same = copy(&prng);
+        let mut bytes = [0u8; 20];
+        prng.fill_bytes(&mut bytes);
+        let expected: Vec<u8> = (0..3).flat_map(|_| same.u64().to_le_bytes()).take(20).collect();
+        assert_eq!(bytes.to_vec(), expected);
+    }
+
+    #[test]
+    fn shuffle_is_deterministic_by_seed() {
+        let shuffled = |seed: u64| {
+            let mut cards: Vec<u32> = (0..52).collect();
+            cards.shuffle(&mut FastPrng::seed_from_u64(seed));
+            cards
+        };
+        let cards = shuffled(7);
+        assert_eq!(cards, shuffled(7));
+        assert_ne!(cards, shuffled(8));
+        assert_ne!(cards, (0..52).collect::<Vec<u32>>());
+        let mut sorted = cards.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..52).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn distributions_sample_deterministically() {
+        let normal = Normal::new(10.0, 2.0).unwrap();
+        let samples = |seed: u64| {
+            let mut prng = FastPrng::seed_from_u64(seed);
+            (0..16).map(|_| normal.sample(&mut prng)).collect::<Vec<f64>>()
+        };
+        assert_eq!(samples(7), samples(7));
+        assert_ne!(samples(7), samples(8));
+        let mut prng = FastPrng::seed_from_u64(7);
+        let mean = (0..10_000).map(|_| normal.sample(&mut prng)).sum::<f64>() / 10_000.0;
+        assert!((mean - 10.0).abs() < 0.1, "{mean}");
+        assert!((0..1_000).all(|_| prng.random_range(3..9) < 9));
+    }
+
+    /// A stream with two seeds stacked, so the whole state is in play
+    fn stacked() -> FastPrng {
+        let mut prng = FastPrng::from_bud(Bud::Pi, 7);
+        prng.u64();
+        prng.push(11);
+        prng.u64();
+        prng.push(12);
+        prng
+    }
+
+    /// Draws, then pops the stack and draws again
+    fn draws_and_pops(prng: &mut FastPrng) -> Vec<u64> {
+        let mut draws: Vec<u64> = (0..4).map(|_| prng.u64()).collect();
+        prng.pop();
+        draws.extend((0..4).map(|_| prng.u64()));
+        prng.pop();
+        draws.extend((0..4).map(|_| prng.u64()));
+        draws
+    }
+
+    #[test]
+    fn bytes_round_trip_the_stack() {
+        let prng = stacked();
+        let bytes = prng.to_bytes();
+        assert_eq!(bytes.len(), 28 + 2 * 16);
+        let mut back = FastPrng::from_bytes(&bytes).unwrap();
+        assert_eq!(back.to_bytes(), bytes);
+        assert_eq!(back.bud(), Bud::Pi.value());
+        assert_eq!(draws_and_pops(&mut back), draws_and_pops(&mut copy(&prng)));
+    }
+
+    #[test]
+    fn from_bytes_rejects_other_lengths() {
+        let bytes = stacked().to_bytes();
+        assert!(FastPrng::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+        assert!(FastPrng::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_none());
+        assert!(FastPrng::from_bytes(&bytes[..20]).is_none());
+        assert!(FastPrng::from_bytes(&[]).is_none());
+    }
+
+    #[test]
+    fn loading_refuses_an_even_bud() {
+        let prng = stacked();
+        let mut bytes = prng.to_bytes();
+        bytes[16] &= !1;
+        assert!(FastPrng::with_bud(u64::from_le_bytes(bytes[16..24].try_into().unwrap()), 7).is_none());
+        assert!(FastPrng::from_bytes(&bytes).is_none());
+
+        let mut json: serde_json::Value = serde_json::to_value(&prng).unwrap();
+        json["bud"] = serde_json::json!(Bud::Pi.value() & !1);
+        let loaded = serde_json::from_value::<FastPrng>(json);
+        assert!(loaded.is_err_and(|err| err.to_string().contains("is even")));
+    }
+
+    #[test]
+    fn serde_round_trips_the_stack() {
+        let prng = stacked();
+        let json = serde_json::to_string(&prng).unwrap();
+        let mut back: FastPrng = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_bytes(), prng.to_bytes());
+        assert_eq!(draws_and_pops(&mut back), draws_and_pops(&mut copy(&prng)));
+    }
+}
@@ -18,3 +18,7 @@
  *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
  */
 pub mod fast_prng;
+#[cfg(any(test, feature = "prng_stats"))]
+pub mod prng_stats;
+pub mod prng_trait;
+pub mod rec_prng;
@@ -0,0 +1,348 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::prng::{fast_prng::FastPrng, prng_trait::IPrng};
+use statrs::{
+    distribution::{ChiSquared, ContinuousCDF, DiscreteCDF, Poisson},
+    function::erf::erfc
+};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter}
+};
+
+/// buckets for the f64 and below checks
+const BUCKETS: u64 = 100;
+/// below(n) with n not a power of 2, where a modulo would be biased
+const ODD_BOUND: u64 = 100;
+const WEIGHTS: [f64; 4] = [1.0, 2.0, 3.0, 4.0];
+
+/// bits of each draw paired by the serial test
+const SERIAL_BITS: u32 = 4;
+/// birthdays per year, days per year is 2^BIRTHDAY_BITS,
+/// so each year expects 512^3 / 2^26 = 2 repeated spacings
+const BIRTHDAYS: usize = 512;
+const BIRTHDAY_BITS: u32 = 24;
+/// the gap test counts draws between hits below GAP_HIT
+const GAP_HIT: f64 = 0.25;
+/// gaps this long or longer share the last bucket
+const GAP_MAX: usize = 16;
+/// draws that tell apart two seeds
+const ALIAS_DRAWS: usize = 4;
+
+/// chi-square p-value that counts were drawn with the expected probabilities<br>
+/// Near 0 means the draws are off, use samples of at least 5 per bucket.
+pub fn chi_square_p(counts: &[u64], expected: &[f64]) -> f64 {
+    let total: u64 = counts.iter().sum();
+    let chi_sq: f64 = counts
+        .iter()
+        .zip(expected)
+        .map(|(count, p)| {
+            let want = total as f64 * p;
+            (*count as f64 - want).powi(2) / want
+        })
+        .sum();
+    ChiSquared::new((counts.len() - 1) as f64).unwrap().sf(chi_sq)
+}
+
+pub fn chi_square_uniform_p(counts: &[u64]) -> f64 {
+    let expected = vec![1.0 / counts.len() as f64; counts.len()];
+    chi_square_p(counts, &expected)
+}
+
+/// chi-square p-values of the IPrng helpers,
+/// each should rarely fall below 0.01
+#[derive(Clone, Debug)]
+pub struct Uniformity {
+    pub u8_p: f64,
+    pub below_p: f64,
+    pub f64_p: f64,
+    pub weighted_p: f64,
+}
+
+impl Uniformity {
+    pub fn min_p(&self) -> f64 {
+        [self.u8_p, self.below_p, self.f64_p, self.weighted_p]
+            .into_iter()
+            .fold(1.0, f64::min)
+    }
+
+    pub fn passes(&self, alpha: f64) -> bool {
+        alpha < self.min_p()
+    }
+}
+
+impl Display for Uniformity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "u8 p {:.4} below p {:.4} f64 p {:.4} weighted p {:.4}",
+            self.u8_p, self.below_p, self.f64_p, self.weighted_p
+        )
+    }
+}
+
+/// Draws samples from each helper and tests the counts
+pub fn uniformity<P: IPrng>(prng: &mut P, samples: u64) -> Uniformity {
+    let mut u8s = vec![0u64; 256];
+    (0..samples).for_each(|_| u8s[prng.u8() as usize] += 1);
+
+    let mut belows = vec![0u64; ODD_BOUND as usize];
+    (0..samples).for_each(|_| belows[prng.below(ODD_BOUND) as usize] += 1);
+
+    let mut f64s = vec![0u64; BUCKETS as usize];
+    (0..samples).for_each(|_| f64s[(prng.f64() * BUCKETS as f64) as usize] += 1);
+
+    let mut weighted = vec![0u64; WEIGHTS.len()];
+    (0..samples).for_each(|_| weighted[prng.weighted(&WEIGHTS).unwrap()] += 1);
+    let total: f64 = WEIGHTS.iter().sum();
+    let expected: Vec<f64> = WEIGHTS.iter().map(|weight| weight / total).collect();
+
+    Uniformity {
+        u8_p: chi_square_uniform_p(&u8s),
+        below_p: chi_square_uniform_p(&belows),
+        f64_p: chi_square_uniform_p(&f64s),
+        weighted_p: chi_square_p(&weighted, &expected),
+    }
+}
+
+/// p-values of a battery of tests on the u64 stream,
+/// each should rarely fall below 0.01
+#[derive(Clone, Debug)]
+pub struct PrngReport {
+    /// ones against zeros over every bit
+    pub frequency_p: f64,
+    /// pairs of high nibbles of consecutive draws
+    pub serial_p: f64,
+    /// runs of equal bits over every bit
+    pub runs_p: f64,
+    /// Marsaglia's repeated spacings of sorted birthdays
+    pub birthday_p: f64,
+    /// lengths between draws below GAP_HIT
+    pub gap_p: f64,
+}
+
+impl PrngReport {
+    pub fn min_p(&self) -> f64 {
+        [self.frequency_p, self.serial_p, self.runs_p, self.birthday_p, self.gap_p]
+            .into_iter()
+            .fold(1.0, f64::min)
+    }
+
+    pub fn passes(&self, alpha: f64) -> bool {
+        alpha < self.min_p()
+    }
+}
+
+impl Display for PrngReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "frequency p {:.4} serial p {:.4} runs p {:.4} birthday p {:.4} gap p {:.4}",
+            self.frequency_p, self.serial_p, self.runs_p, self.birthday_p, self.gap_p
+        )
+    }
+}
+
+/// Runs every test on words draws each
+pub fn report<P: IPrng>(prng: &mut P, words: usize) -> PrngReport {
+    let draws: Vec<u64> = (0..words).map(|_| prng.u64()).collect();
+    PrngReport {
+        frequency_p: frequency_p(&draws),
+        serial_p: serial_p(&draws),
+        runs_p: runs_p(&draws),
+        birthday_p: birthday_p(prng, (words / BIRTHDAYS).max(1)),
+        gap_p: gap_p(prng, words),
+    }
+}
+
+/// Two sided p-value of the count of ones
+pub fn frequency_p(draws: &[u64]) -> f64 {
+    let bits = draws.len() as f64 * 64.0;
+    let ones: u64 = draws.iter().map(|draw| draw.count_ones() as u64).sum();
+    let z = (ones as f64 - bits / 2.0) / (bits / 4.0).sqrt();
+    erfc(z.abs() / std::f64::consts::SQRT_2)
+}
+
+/// chi-square of the high SERIAL_BITS of each draw against the next
+pub fn serial_p(draws: &[u64]) -> f64 {
+    let cells = 1usize << (2 * SERIAL_BITS);
+    let mut pairs = vec![0u64; cells];
+    draws.chunks_exact(2).for_each(|pair| {
+        let cell = (pair[0] >> (64 - SERIAL_BITS)) << SERIAL_BITS | (pair[1] >> (64 - SERIAL_BITS));
+        pairs[cell as usize] += 1;
+    });
+    chi_square_uniform_p(&pairs)
+}
+
+/// NIST runs test over every bit, 0 if the ones are too far off for it
+pub fn runs_p(draws: &[u64]) -> f64 {
+    let bits = draws.len() as f64 * 64.0;
+    let ones: u64 = draws.iter().map(|draw| draw.count_ones() as u64).sum();
+    let pi = ones as f64 / bits;
+    if 2.0 / bits.sqrt() <= (pi - 0.5).abs() {
+        return 0.0;
+    }
+    // bits run from bit 0 to bit 63 of each draw in turn
+    let mut last = draws.first().map_or(0, |draw| draw & 1);
+    let mut runs = 1u64;
+    draws.iter().for_each(|draw| {
+        // a run ends at each bit that differs from the one before it
+        runs += ((draw ^ (draw >> 1)) & !(1 << 63)).count_ones() as u64;
+        runs += (draw & 1 != last) as u64;
+        last = draw >> 63;
+    });
+    let expected = 2.0 * bits * pi * (1.0 - pi);
+    erfc((runs as f64 - expected).abs() / (2.0 * (2.0 * bits).sqrt() * pi * (1.0 - pi)))
+}
+
+/// Two sided Poisson p-value of repeated spacings over years
+pub fn birthday_p<P: IPrng>(prng: &mut P, years: usize) -> f64 {
+    let days = (1u64 << BIRTHDAY_BITS) as f64;
+    let lambda = (BIRTHDAYS as f64).powi(3) / (4.0 * days);
+    let repeats: u64 = (0..years)
+        .map(|_| {
+            let mut birthdays: Vec<u64> = (0..BIRTHDAYS)
+                .map(|_| prng.u64() >> (64 - BIRTHDAY_BITS))
+                .collect();
+            birthdays.sort_unstable();
+            let mut spacings: Vec<u64> = birthdays.windows(2).map(|pair| pair[1] - pair[0]).collect();
+            spacings.sort_unstable();
+            spacings.windows(2).filter(|pair| pair[0] == pair[1]).count() as u64
+        })
+        .sum();
+    let poisson = Poisson::new(lambda * years as f64).unwrap();
+    let below = poisson.cdf(repeats);
+    let above = if repeats == 0 { 1.0 } else { poisson.sf(repeats - 1) };
+    (2.0 * below.min(above)).min(1.0)
+}
+
+/// chi-square of gap lengths against the geometric lengths they should have
+pub fn gap_p<P: IPrng>(prng: &mut P, gaps: usize) -> f64 {
+    let mut lengths = vec![0u64; GAP_MAX + 1];
+    (0..gaps).for_each(|_| {
+        let mut length = 0;
+        while GAP_HIT <= prng.f64() {
+            length += 1;
+        }
+        lengths[length.min(GAP_MAX)] += 1;
+    });
+    let mut expected: Vec<f64> = (0..GAP_MAX)
+        .map(|length| GAP_HIT * (1.0 - GAP_HIT).powi(length as i32))
+        .collect();
+    expected.push((1.0 - GAP_HIT).powi(GAP_MAX as i32));
+    chi_square_p(&lengths, &expected)
+}
+
+/// Seeds whose first words draws fail the report at alpha,
+/// short streams show a poor warm up
+pub fn weak_seeds(seeds: impl IntoIterator<Item = u64>, words: usize, alpha: f64) -> Vec<(u64, PrngReport)> {
+    seeds
+        .into_iter()
+        .filter_map(|seed| {
+            let mut prng = FastPrng::new();
+            prng.seed(seed);
+            let report = report(&mut prng, words);
+            (!report.passes(alpha)).then_some((seed, report))
+        })
+        .collect()
+}
+
+/// Pairs of seeds that give the same stream<br>
+/// seed(0) falls back to the bud, so it always pairs with seed(bud).
+pub fn seed_aliases(seeds: impl IntoIterator<Item = u64>) -> Vec<(u64, u64)> {
+    let mut streams: HashMap<[u64; ALIAS_DRAWS], u64> = HashMap::new();
+    seeds
+        .into_iter()
+        .filter_map(|seed| {
+            let mut prng = FastPrng::new();
+            prng.seed(seed);
+            let stream: [u64; ALIAS_DRAWS] = std::array::from_fn(|_| prng.u64());
+            match streams.get(&stream) {
+                Some(first) => Some((*first, seed)),
+                None => {
+                    streams.insert(stream, seed);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: [u64; 5] = [1, 2, 42, 0xDEAD_BEEF, u64::MAX];
+
+    #[test]
+    fn helpers_are_uniform() {
+        SEEDS.iter().for_each(|seed| {
+            let mut prng = FastPrng::new();
+            prng.seed(*seed);
+            let uniformity = uniformity(&mut prng, 100_000);
+            assert!(uniformity.passes(0.001), "seed {seed}: {uniformity}");
+        });
+    }
+
+    #[test]
+    fn fast_prng_passes_the_report() {
+        SEEDS.iter().for_each(|seed| {
+            let mut prng = FastPrng::new();
+            prng.seed(*seed);
+            let report = report(&mut prng, 100_000);
+            assert!(report.passes(0.001), "seed {seed}: {report}");
+        });
+    }
+
+    #[test]
+    fn no_weak_seeds_at_the_start() {
+        assert!(weak_seeds(1..=32, 10_000, 0.0001).is_empty());
+    }
+
+    #[test]
+    fn seed_zero_aliases_the_bud() {
+        let bud = FastPrng::new().bud();
+        assert_eq!(seed_aliases([0, 1, 2, bud, 3]), vec![(0, bud)]);
+        assert!(seed_aliases(1..=1000).is_empty());
+    }
+
+    /// Forces the top bit of every third draw
+    struct Skewed(FastPrng, u64);
+
+    impl IPrng for Skewed {
+        fn u64(&mut self) -> u64 {
+            self.1 += 1;
+            let draw = self.0.u64();
+            if self.1.is_multiple_of(3) { draw | 1 << 63 } else { draw }
+        }
+    }
+
+    #[test]
+    fn a_skewed_stream_fails() {
+        let mut prng = FastPrng::new();
+        prng.seed(1);
+        let uniformity = uniformity(&mut Skewed(prng, 0), 100_000);
+        assert!(!uniformity.passes(0.001), "{uniformity}");
+        let report = report(&mut Skewed(FastPrng::new(), 0), 100_000);
+        assert!(!report.passes(0.001), "{report}");
+    }
+}
@@ -0,0 +1,194 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use std::ops::Range;
+
+/// 2^-53, one step of a 53 bit mantissa
+const F64_STEP: f64 = 1.0 / (1u64 << 53) as f64;
+/// 2^-24, one step of a 24 bit mantissa
+const F32_STEP: f32 = 1.0 / (1u32 << 24) as f32;
+
+/// Unbiased draws built on a u64 stream<br>
+/// Every helper takes the high bits, the low bits of u64 are weaker.
+pub trait IPrng {
+    fn u64(&mut self) -> u64;
+
+    fn u8(&mut self) -> u8 {
+        (self.u64() >> 56) as u8
+    }
+
+    fn bool(&mut self) -> bool {
+        self.u64() >> 63 == 1
+    }
+
+    /// Random number in [0, 1), never 1
+    fn f64(&mut self) -> f64 {
+        (self.u64() >> 11) as f64 * F64_STEP
+    }
+
+    /// Random number in [0, 1), never 1
+    fn f32(&mut self) -> f32 {
+        (self.u64() >> 40) as f32 * F32_STEP
+    }
+
+    /// Random number in [0, n), Lemire's multiply and reject<br>
+    /// Panics if n is 0
+    fn below(&mut self, n: u64) -> u64 {
+        assert!(0 < n, "below needs n > 0");
+        let mut wide = self.u64() as u128 * n as u128;
+        if (wide as u64) < n {
+            let threshold = n.wrapping_neg() % n;
+            while (wide as u64) < threshold {
+                wide = self.u64() as u128 * n as u128;
+            }
+        }
+        (wide >> 64) as u64
+    }
+
+    /// Panics if the range is empty
+    fn range_u64(&mut self, range: Range<u64>) -> u64 {
+        assert!(range.start < range.end, "empty range");
+        range.start + self.below(range.end - range.start)
+    }
+
+    /// Panics if the range is empty
+    fn range_i64(&mut self, range: Range<i64>) -> i64 {
+        assert!(range.start < range.end, "empty range");
+        let span = range.end.wrapping_sub(range.start) as u64;
+        range.start.wrapping_add(self.below(span) as i64)
+    }
+
+    /// Panics if the range is empty
+    fn range_usize(&mut self, range: Range<usize>) -> usize {
+        self.range_u64(range.start as u64..range.end as u64) as usize
+    }
+
+    /// Panics if the range is empty<br>
+    /// Rounding that lands on end is pulled back below it
+    fn range_f64(&mut self, range: Range<f64>) -> f64 {
+        assert!(range.start < range.end, "empty range");
+        let value = range.start + self.f64() * (range.end - range.start);
+        value.min(range.end.next_down())
+    }
+
+    /// true with probability p, a p outside [0, 1] acts clamped
+    fn chance(&mut self, p: f64) -> bool {
+        self.f64() < p
+    }
+
+    /// An index picked in proportion to its weight<br>
+    /// Weights that are not positive are never picked,
+    /// None if no weight is positive.
+    fn weighted(&mut self, weights: &[f64]) -> Option<usize> {
+        let usable = |weight: f64| weight.is_finite() && 0.0 < weight;
+        let total: f64 = weights.iter().copied().filter(|weight| usable(*weight)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut target = self.f64() * total;
+        let mut last = None;
+        for (index, weight) in weights.iter().copied().enumerate() {
+            if !usable(weight) {
+                continue;
+            }
+            if target < weight {
+                return Some(index);
+            }
+            target -= weight;
+            last = Some(index);
+        }
+        // float error left target past the last weight
+        last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Draws the same word forever
+    struct Fixed(u64);
+
+    impl IPrng for Fixed {
+        fn u64(&mut self) -> u64 {
+            self.0
+        }
+    }
+
+    /// Draws its words in turn
+    struct Cycle(Vec<u64>, usize);
+
+    impl IPrng for Cycle {
+        fn u64(&mut self) -> u64 {
+            self.1 += 1;
+            self.0[(self.1 - 1) % self.0.len()]
+        }
+    }
+
+    #[test]
+    fn floats_stay_below_1() {
+        assert!(Fixed(u64::MAX).f64() < 1.0);
+        assert!(Fixed(u64::MAX).f32() < 1.0);
+        assert_eq!(Fixed(0).f64(), 0.0);
+    }
+
+    #[test]
+    fn below_1_is_0() {
+        [0, 1, u64::MAX].into_iter().for_each(|word| assert_eq!(Fixed(word).below(1), 0));
+    }
+
+    #[test]
+    fn below_rejects_the_biased_low_words() {
+        // 0 falls in the rejected zone for n = 3, the next word is taken
+        let mut prng = Cycle(vec![0, u64::MAX], 0);
+        assert_eq!(prng.below(3), 2);
+        assert_eq!(prng.1, 2);
+    }
+
+    #[test]
+    fn wide_ranges_stay_in_range() {
+        // a stream of 0 is always rejected by a range this wide
+        [1, u64::MAX / 2, u64::MAX].into_iter().for_each(|word| {
+            let value = Fixed(word).range_i64(i64::MIN..i64::MAX);
+            assert!((i64::MIN..i64::MAX).contains(&value), "{word} gave {value}");
+            let value = Fixed(word).range_u64(0..u64::MAX);
+            assert!(value < u64::MAX);
+            let value = Fixed(word).range_f64(-1.0..1.0);
+            assert!((-1.0..1.0).contains(&value));
+        });
+        assert_eq!(Fixed(1).range_i64(i64::MIN..i64::MAX), i64::MIN);
+        assert_eq!(Fixed(u64::MAX).range_i64(i64::MIN..i64::MAX), i64::MAX - 1);
+    }
+
+    #[test]
+    fn weighted_needs_a_positive_weight() {
+        assert_eq!(Fixed(0).weighted(&[0.0, -1.0]), None);
+        assert_eq!(Fixed(0).weighted(&[f64::NAN, f64::INFINITY]), None);
+        assert_eq!(Fixed(0).weighted(&[]), None);
+        assert_eq!(Fixed(u64::MAX).weighted(&[0.0, 1.0, -1.0]), Some(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn below_0_panics() {
+        Fixed(0).below(0);
+    }
+}
@@ -0,0 +1,176 @@
+/*
+ * Copyright 2005-2024 Daniel Chay
+ *
+ *
+ *  This file is part of Isomech.
+ *
+ *  Isomech is free software  you can redistribute it and/or modify
+ *  it under the terms of the GNU Lesser General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  Isomech is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU Lesser General Public License for more details.
+ *
+ *  You should have received a copy of the GNU Lesser General Public License
+ *  along with Isomech.  If not, see <http://www.gnu.org/licenses/>.
+ */
+#![allow(unused)]
+
+use crate::prng::{fast_prng::split_mix, prng_trait::IPrng};
+use rand::RngCore;
+
+/// Records every u64 drawn from an IPrng.<br>
+/// Lockstep peers can swap digests each tick and,
+/// once they differ, swap logs to find the draw where they split.
+pub struct RecPrng<P: IPrng> {
+    prng: P,
+    log: Vec<u64>,
+    digest: u64,
+}
+
+impl<P: IPrng> RecPrng<P> {
+    pub fn new(prng: P) -> Self {
+        RecPrng {
+            prng,
+            log: vec![],
+            digest: 0,
+        }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.prng
+    }
+
+    pub fn into_inner(self) -> P {
+        self.prng
+    }
+
+    pub fn log(&self) -> &[u64] {
+        &self.log
+    }
+
+    /// Covers every draw so far, even those cleared from the log
+    pub fn digest(&self) -> u64 {
+        self.digest
+    }
+
+    /// Drops the log but keeps the digest running
+    pub fn clear_log(&mut self) {
+        self.log.clear();
+    }
+
+    /// The first draw where the logs differ,
+    /// or where the shorter one ends, None if they match
+    pub fn diverge(&self, other: &[u64]) -> Option<usize> {
+        diverge(&self.log, other)
+    }
+}
+
+/// The first index where two draw logs differ,
+/// or where the shorter one ends, None if they match
+pub fn diverge(a: &[u64], b: &[u64]) -> Option<usize> {
+    a.iter()
+        .zip(b)
+        .position(|(a_draw, b_draw)| a_draw != b_draw)
+        .or_else(|| (a.len() != b.len()).then(|| a.len().min(b.len())))
+}
+
+impl<P: IPrng> IPrng for RecPrng<P> {
+    fn u64(&mut self) -> u64 {
+        let draw = self.prng.u64();
+        self.log.push(draw);
+        self.digest = split_mix(self.digest ^ draw);
+        draw
+    }
+}
+
+/// So rand and rand_distr draws are recorded too
+impl<P: IPrng> RngCore for RecPrng<P> {
+    fn next_u32(&mut self) -> u32 {
+        (self.u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.u64()
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        dst.chunks_mut(8).for_each(|chunk| {
+            let bytes = self.u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prng::fast_prng::FastPrng;
+    use rand::{seq::SliceRandom, SeedableRng};
+
+    /// A tick of mixed draws, through IPrng and rand alike
+    fn tick<P: IPrng>(prng: &mut RecPrng<P>) {
+        prng.range_u64(0..100);
+        prng.f64();
+        prng.chance(0.5);
+        let mut cards: Vec<u32> = (0..8).collect();
+        cards.shuffle(prng);
+    }
+
+    #[test]
+    fn log_holds_every_draw() {
+        let mut rec = RecPrng::new(FastPrng::seed_from_u64(7));
+        let draws: Vec<u64> = (0..16).map(|_| rec.u64()).collect();
+        assert_eq!(rec.log(), draws.as_slice());
+        let mut plain = FastPrng::seed_from_u64(7);
+        assert!(draws.iter().all(|draw| *draw == plain.u64()));
+    }
+
+    #[test]
+    fn a_replay_reproduces_the_log_and_digest() {
+        let mut first = RecPrng::new(FastPrng::seed_from_u64(7));
+        let mut replay = RecPrng::new(FastPrng::seed_from_u64(7));
+        (0..10).for_each(|_| tick(&mut first));
+        (0..10).for_each(|_| tick(&mut replay));
+        assert!(!first.log().is_empty());
+        assert_eq!(replay.log(), first.log());
+        assert_eq!(replay.digest(), first.digest());
+        assert_eq!(replay.diverge(first.log()), None);
+    }
+
+    #[test]
+    fn diverge_reports_the_first_differing_draw() {
+        let mut rec = RecPrng::new(FastPrng::seed_from_u64(7));
+        (0..8).for_each(|_| tick(&mut rec));
+        let log = rec.log().to_vec();
+        let mut desynced = log.clone();
+        desynced[5] ^= 1;
+        desynced[9] ^= 1;
+        assert_eq!(rec.diverge(&desynced), Some(5));
+        assert_eq!(rec.diverge(&log[..4]), Some(4));
+        assert_eq!(diverge(&log[..4], &log), Some(4));
+        assert_eq!(rec.diverge(&log), None);
+
+        let mut other = RecPrng::new(FastPrng::seed_from_u64(8));
+        tick(&mut other);
+        assert_eq!(diverge(other.log(), &log), Some(0));
+    }
+
+    #[test]
+    fn the_digest_outlives_a_cleared_log() {
+        let mut a = RecPrng::new(FastPrng::seed_from_u64(7));
+        let mut b = RecPrng::new(FastPrng::seed_from_u64(7));
+        (0..4).for_each(|_| tick(&mut a));
+        (0..4).for_each(|_| tick(&mut b));
+        a.clear_log();
+        assert!(a.log().is_empty());
+        assert_eq!(a.digest(), b.digest());
+        tick(&mut a);
+        tick(&mut b);
+        assert_eq!(a.digest(), b.digest());
+        assert!(b.log().ends_with(a.log()));
+    }
+}
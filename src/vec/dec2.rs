@@ -40,10 +40,10 @@ pub struct Dec2 {
 
 impl Dec2 {
     pub fn x(&self) -> &Dec {
-        &*self.x
+        &self.x
     }
     pub fn y(&self) -> &Dec {
-        &*self.y
+        &self.y
     }
 
     pub fn new(x: Arc<Dec>, y: Arc<Dec>) -> Self {
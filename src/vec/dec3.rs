@@ -31,13 +31,13 @@ pub struct Dec3 {
 
 impl Dec3 {
     pub fn x(&self) -> &Dec {
-        &*self.x
+        &self.x
     }
     pub fn y(&self) -> &Dec {
-        &*self.y
+        &self.y
     }
     pub fn z(&self) -> &Dec {
-        &*self.z
+        &self.z
     }
 
     pub fn new(x: Arc<Dec>, y: Arc<Dec>, z: Arc<Dec>) -> Self {
@@ -31,7 +31,6 @@ use crate::vec::dec3::Dec3;
 /// 0 0     Ǫ   Ǫ   Ǫ   Ǫ   Ǫ   0 0
 ///     0 1 2 3 4 5 6 7 8 9 A B
 /// U  0                      1
-
 #[allow(dead_code)]
 pub struct VertData {
     uv_land: Dec2,